@@ -9,6 +9,7 @@ extern crate serde_json;
 pub enum CfgError {
     IO(String),
     Parse(String),
+    Invalid(String),
 }
 
 fn load<T: serde::Deserialize>(path: &str) -> Result<T, CfgError> {
@@ -35,6 +36,22 @@ pub mod assets {
     }
 }
 
+/// Calls the macro with the names of the keys which can be bound, as identifiers. `ui::KEY_NAMES`
+/// and the frontends' key codes are generated from this list.
+#[macro_export]
+macro_rules! with_key_names {
+    ($m:ident) => {
+        $m!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+            Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+            Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+            F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+            Up, Down, Left, Right, PageUp, PageDown, Home, End, Insert, Delete,
+            Escape, Return, Space, Tab, Back,
+            Period, Comma, Slash, Semicolon, Apostrophe, Minus, Equals,
+            LBracket, RBracket, Backslash, Grave)
+    }
+}
+
 pub mod ui {
     use std::collections::HashMap;
 
@@ -64,16 +81,379 @@ pub mod ui {
         pub tiles: HashMap<String, UiMapTileCfg>,
     }
 
+    macro_rules! key_name_list {
+        ($($key:ident),*) => { &[$(stringify!($key)),*] }
+    }
+
+    /// Names of the keys which can be bound, the same as the names of glutin's `VirtualKeyCode`.
+    pub const KEY_NAMES: &'static [&'static str] = with_key_names!(key_name_list);
+
+    /// A key with modifiers, written as e.g. "Shift+Period" or "Ctrl+Q" in the keymap.
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct KeyCfg {
+        /// One of `KEY_NAMES`.
+        pub name: String,
+        pub shift: bool,
+        pub ctrl: bool,
+    }
+
+    impl KeyCfg {
+        pub fn parse(s: &str) -> Result<KeyCfg, super::CfgError> {
+            use super::CfgError;
+
+            let mut parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+            let name = parts.pop().unwrap_or("");
+            let (mut shift, mut ctrl) = (false, false);
+
+            for m in parts {
+                match m {
+                    "Shift" => shift = true,
+                    "Ctrl" => ctrl = true,
+                    other => return Err(CfgError::Invalid(format!("Unknown key modifier '{}' in '{}'", other, s))),
+                }
+            }
+
+            if !KEY_NAMES.contains(&name) {
+                return Err(CfgError::Invalid(format!("Unknown key name '{}' in '{}'", name, s)));
+            }
+
+            Ok(KeyCfg {
+                name: name.to_owned(),
+                shift: shift,
+                ctrl: ctrl,
+            })
+        }
+    }
+
+    /// Bindings shared by all presets.
+    const COMMON_KEYS: &'static [(&'static str, &'static str)] = &[
+        ("Up", "move_n"), ("Right", "move_e"), ("Down", "move_s"), ("Left", "move_w"),
+        ("Period", "wait"), ("Space", "wait"),
+        ("Shift+Comma", "ascend"), ("Shift+Period", "descend"), ("PageUp", "ascend"), ("PageDown", "descend"),
+        ("G", "pick_up"), ("I", "inventory"), ("X", "drop"), ("O", "open"), ("F", "interact"),
+        ("Shift+R", "new_level"), ("M", "history"), ("LBracket", "scroll_up"), ("RBracket", "scroll_down"),
+        ("Escape", "quit"), ("Ctrl+Q", "quit"),
+    ];
+
+    const DEFAULT_KEYS: &'static [(&'static str, &'static str)] = &[
+        ("W", "move_n"), ("E", "move_ne"), ("D", "move_e"), ("C", "move_se"),
+        ("S", "move_s"), ("Z", "move_sw"), ("A", "move_w"), ("Q", "move_nw"),
+        ("K", "close"),
+    ];
+
+    const VI_KEYS: &'static [(&'static str, &'static str)] = &[
+        ("K", "move_n"), ("U", "move_ne"), ("L", "move_e"), ("N", "move_se"),
+        ("J", "move_s"), ("B", "move_sw"), ("H", "move_w"), ("Y", "move_nw"),
+        ("C", "close"),
+    ];
+
+    const NUMPAD_KEYS: &'static [(&'static str, &'static str)] = &[
+        ("Numpad8", "move_n"), ("Numpad9", "move_ne"), ("Numpad6", "move_e"), ("Numpad3", "move_se"),
+        ("Numpad2", "move_s"), ("Numpad1", "move_sw"), ("Numpad4", "move_w"), ("Numpad7", "move_nw"),
+        ("Numpad5", "wait"), ("K", "close"),
+    ];
+
+    fn default_preset() -> String {
+        "default".to_owned()
+    }
+
+    /// Maps keys to the names of the UI's actions. The bindings of the preset are applied first,
+    /// the own bindings can override them.
+    #[derive(Clone, Deserialize)]
+    pub struct KeymapCfg {
+        /// "default" (WASD and the keys around), "vi" (HJKL and YUBN) or "numpad".
+        #[serde(default="default_preset")]
+        pub preset: String,
+        /// Key, e.g. "Shift+G", to the action name.
+        #[serde(default)]
+        pub bindings: HashMap<String, String>,
+    }
+
+    impl Default for KeymapCfg {
+        fn default() -> Self {
+            KeymapCfg {
+                preset: default_preset(),
+                bindings: HashMap::new(),
+            }
+        }
+    }
+
+    impl KeymapCfg {
+        /// Returns all bindings with the keys parsed.
+        pub fn bindings(&self) -> Result<HashMap<KeyCfg, String>, super::CfgError> {
+            let preset = match self.preset.as_ref() {
+                "default" => DEFAULT_KEYS,
+                "vi" => VI_KEYS,
+                "numpad" => NUMPAD_KEYS,
+                other => return Err(super::CfgError::Invalid(format!("Unknown keymap preset: '{}'", other))),
+            };
+
+            let mut bindings = HashMap::new();
+            for &(key, action) in COMMON_KEYS.iter().chain(preset.iter()) {
+                bindings.insert(try!(KeyCfg::parse(key)), action.to_owned());
+            }
+
+            for (key, action) in &self.bindings {
+                bindings.insert(try!(KeyCfg::parse(key)), action.clone());
+            }
+
+            Ok(bindings)
+        }
+    }
+
     #[derive(Deserialize)]
     pub struct UiCfg {
         pub map: UiMapCfg,
+        #[serde(default)]
+        pub keymap: KeymapCfg,
     }
 
+    /// Loads the config, the keymap's keys are checked.
     pub fn load(path: &str) -> Result<UiCfg, super::CfgError> {
+        let c: UiCfg = try!(super::load(path));
+        try!(c.keymap.bindings());
+        Ok(c)
+    }
+}
+
+pub mod terrain {
+    fn default_movement_cost() -> f32 {
+        1.0
+    }
+
+    #[derive(Clone, Deserialize)]
+    pub struct TerrainCfg {
+        pub name: String,
+        #[serde(default)]
+        pub description: String,
+        /// Map cells of this terrain type. The first one is used when a map is generated.
+        pub cells: Vec<u8>,
+        pub passable: bool,
+        pub blocks_sight: bool,
+        #[serde(default="default_movement_cost")]
+        pub movement_cost: f32,
+        pub fg: Option<[u8; 4]>,
+        pub bg: Option<[u8; 4]>,
+        /// Name of the terrain this one turns into when opened, e.g. a closed door.
+        pub opens_to: Option<String>,
+        /// Name of the terrain this one turns into when closed, e.g. an open door.
+        pub closes_to: Option<String>,
+        /// "up" or "down" for stairs leading to the next z-level.
+        pub stairs: Option<String>,
+        /// Script whose `on_enter` is called when an entity steps on this terrain.
+        pub trigger: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct TerrainSetCfg {
+        /// Name of the terrain used for the cells not listed anywhere.
+        pub default: String,
+        pub terrains: Vec<TerrainCfg>,
+    }
+
+    pub fn load(path: &str) -> Result<TerrainSetCfg, super::CfgError> {
         super::load(path)
     }
 }
 
+pub mod monsters {
+    fn default_idle() -> String {
+        "idle".to_owned()
+    }
+
+    fn default_chase() -> bool {
+        true
+    }
+
+    #[derive(Clone, Deserialize)]
+    pub struct AiCfg {
+        /// What the monster does when there's nothing else to do: "idle" or "wander".
+        #[serde(default="default_idle")]
+        pub idle: String,
+        /// Chase the player when seen.
+        #[serde(default="default_chase")]
+        pub chase: bool,
+        /// Flee from the player when the health drops below this fraction of the maximum.
+        pub flee_below: Option<f32>,
+        /// Stay this far from the player, used by ranged attackers.
+        pub keep_distance: Option<u32>,
+    }
+}
+
+pub mod items {
+    fn default_kind() -> String {
+        "misc".to_owned()
+    }
+
+    #[derive(Clone, Deserialize)]
+    pub struct ItemCfg {
+        pub name: String,
+        /// CP437 code of the item's glyph.
+        pub glyph: u8,
+        pub weight: f32,
+        #[serde(default)]
+        pub stackable: bool,
+        /// "misc", "potion", "weapon" or "armor".
+        #[serde(default="default_kind")]
+        pub kind: String,
+        /// Health restored by a potion.
+        #[serde(default)]
+        pub heal: i32,
+        /// Power added by a weapon.
+        #[serde(default)]
+        pub power: i32,
+        /// Defense added by an armor.
+        #[serde(default)]
+        pub defense: i32,
+        /// Script whose `on_use` is called when the item is used. The item is used up.
+        pub script: Option<String>,
+    }
+}
+
+pub mod prefabs {
+    use super::CfgError;
+    use super::monsters::AiCfg;
+    use super::items::ItemCfg;
+
+    fn default_speed() -> u32 {
+        100
+    }
+
+    fn default_accuracy() -> f32 {
+        0.7
+    }
+
+    #[derive(Clone, Deserialize)]
+    pub struct EnergyCfg {
+        /// Energy gained every turn, 100 is the normal speed.
+        #[serde(default="default_speed")]
+        pub speed: u32,
+    }
+
+    #[derive(Clone, Deserialize)]
+    pub struct CombatCfg {
+        /// A hit deals from 1 to `power` damage.
+        pub power: i32,
+        #[serde(default)]
+        pub defense: i32,
+        /// Chance to hit a target without defense.
+        #[serde(default="default_accuracy")]
+        pub accuracy: f32,
+    }
+
+    #[derive(Clone, Deserialize)]
+    pub struct LightCfg {
+        pub radius: u32,
+        pub color: [f32; 3],
+        pub intensity: f32,
+        #[serde(default)]
+        pub flicker: f32,
+    }
+
+    /// Components of an entity, each one is optional. Flags like `player` add the component when
+    /// they're `true`.
+    #[derive(Clone, Default, Deserialize)]
+    pub struct ComponentsCfg {
+        pub name: Option<String>,
+        /// CP437 code of the entity's glyph.
+        pub glyph: Option<u8>,
+        pub player: Option<bool>,
+        pub blocks_movement: Option<bool>,
+        pub sight_radius: Option<u32>,
+        pub energy: Option<EnergyCfg>,
+        pub health: Option<i32>,
+        pub combat: Option<CombatCfg>,
+        pub ai: Option<AiCfg>,
+        /// Script whose `on_turn` controls the entity.
+        pub script: Option<String>,
+        pub item: Option<ItemCfg>,
+        /// Maximum weight of the carried items.
+        pub inventory: Option<f32>,
+        pub light: Option<LightCfg>,
+    }
+
+    impl ComponentsCfg {
+        /// Adds the parent's components which this one doesn't have.
+        fn inherit(&mut self, parent: &ComponentsCfg) {
+            macro_rules! inherit {
+                ($($field:ident),*) => {
+                    $(
+                        if self.$field.is_none() {
+                            self.$field = parent.$field.clone();
+                        }
+                    )*
+                }
+            }
+
+            inherit!(name, glyph, player, blocks_movement, sight_radius, energy, health, combat, ai, script, item,
+                     inventory, light);
+        }
+    }
+
+    /// A named entity type. A prefab with a parent has all of the parent's components, its own
+    /// components replace the parent's ones.
+    #[derive(Clone, Deserialize)]
+    pub struct PrefabCfg {
+        pub name: String,
+        pub parent: Option<String>,
+        #[serde(default)]
+        pub components: ComponentsCfg,
+    }
+
+    #[derive(Clone, Default, Deserialize)]
+    pub struct PrefabSetCfg {
+        pub prefabs: Vec<PrefabCfg>,
+    }
+
+    impl PrefabSetCfg {
+        pub fn get(&self, name: &str) -> Option<&PrefabCfg> {
+            self.prefabs.iter().find(|p| p.name == name)
+        }
+
+        /// Returns the components of the prefab with the inherited ones.
+        pub fn resolve(&self, name: &str) -> Result<ComponentsCfg, CfgError> {
+            let mut prefab = match self.get(name) {
+                Some(p) => p,
+                None => return Err(CfgError::Invalid(format!("Unknown prefab: '{}'", name))),
+            };
+
+            let mut components = prefab.components.clone();
+            let mut chain = vec![name];
+
+            while let Some(parent) = prefab.parent.clone() {
+                if chain.iter().any(|n| *n == parent) {
+                    return Err(CfgError::Invalid(format!("Prefab '{}' inherits from itself", name)));
+                }
+
+                prefab = match self.get(&parent) {
+                    Some(p) => p,
+                    None => {
+                        return Err(CfgError::Invalid(format!("Unknown parent '{}' of prefab '{}'", parent, name)))
+                    },
+                };
+                components.inherit(&prefab.components);
+                chain.push(&prefab.name);
+            }
+
+            Ok(components)
+        }
+    }
+
+    /// Loads the prefabs, all of them are checked to resolve.
+    pub fn load(path: &str) -> Result<PrefabSetCfg, CfgError> {
+        let c: PrefabSetCfg = try!(super::load(path));
+
+        for (i, p) in c.prefabs.iter().enumerate() {
+            if c.prefabs[..i].iter().any(|other| other.name == p.name) {
+                return Err(CfgError::Invalid(format!("Duplicate prefab: '{}'", p.name)));
+            }
+            try!(c.resolve(&p.name));
+        }
+
+        Ok(c)
+    }
+}
+
 impl From<serde_json::error::Error> for CfgError {
     fn from(e: serde_json::error::Error) -> Self {
         CfgError::Parse(format!("{:?}", e))
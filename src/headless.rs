@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use world::{self, cp437, replay, PlayerCommand, Direction};
+use world::tile::{Effect, Tile};
+use world::components::PLAYER_MARK;
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "w" | "up" | "n" => Some(Direction::North),
+        "e" | "ne" => Some(Direction::NorthEast),
+        "d" | "right" => Some(Direction::East),
+        "c" | "se" => Some(Direction::SouthEast),
+        "s" | "down" => Some(Direction::South),
+        "z" | "sw" => Some(Direction::SouthWest),
+        "a" | "left" => Some(Direction::West),
+        "q" | "nw" => Some(Direction::NorthWest),
+        _ => None,
+    }
+}
+
+/// Items are chosen by the letters shown in the UI's inventory, `a` is the first item.
+fn parse_item(s: &str) -> Option<usize> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'...'z'), None) => Some((c as u8 - b'a') as usize),
+        _ => None,
+    }
+}
+
+/// Parses a command of a script. Commands are named after the keys of the UI or spelled out.
+/// Commands which need a direction take it as an argument, e.g. `open left`, commands which
+/// need an item take its letter, e.g. `drop b`.
+fn parse_command(s: &str) -> Option<PlayerCommand> {
+    let mut words = s.split_whitespace();
+    let name = match words.next() {
+        Some(n) => n,
+        None => return None,
+    };
+    let arg = words.next();
+    if words.next().is_some() {
+        return None;
+    }
+
+    match (name, arg) {
+        ("wait", None) | (".", None) => Some(PlayerCommand::Wait),
+        ("ascend", None) | ("<", None) => Some(PlayerCommand::Ascend),
+        ("descend", None) | (">", None) => Some(PlayerCommand::Descend),
+        ("pickup", None) | ("g", None) => Some(PlayerCommand::PickUp),
+        ("drop", Some(a)) => parse_item(a).map(PlayerCommand::Drop),
+        ("use", Some(a)) => parse_item(a).map(PlayerCommand::Use),
+        ("equip", Some(a)) => parse_item(a).map(PlayerCommand::Equip),
+        ("open", Some(a)) => parse_direction(a).map(PlayerCommand::Open),
+        ("close", Some(a)) => parse_direction(a).map(PlayerCommand::Close),
+        ("interact", Some(a)) => parse_direction(a).map(PlayerCommand::Interact),
+        (n, None) => parse_direction(n).map(PlayerCommand::Move),
+        _ => None,
+    }
+}
+
+fn tile_char(t: &Tile) -> char {
+    if let Some(ref fx) = t.effects {
+        for e in fx {
+            if let Effect::Marked(mark) = *e {
+                if mark == PLAYER_MARK {
+                    return '@';
+                }
+                return cp437::to_char(mark);
+            }
+        }
+    }
+
+    if !t.explored {
+        return ' ';
+    }
+
+    cp437::to_char(t.ground)
+}
+
+fn print_view<W: Write>(out: &mut W, world: &world::World) -> io::Result<()> {
+    try!(writeln!(out, "turn {}", world.turn()));
+
+    if let Some(ref view) = *world.last_render().lock().unwrap() {
+        let (sx, _, _) = view.size();
+        let mut line = String::new();
+
+        for (x, _, _, t) in view.iter() {
+            line.push(tile_char(t));
+
+            if x == sx - 1 {
+                try!(writeln!(out, "{}", line.trim_right()));
+                line.clear();
+            }
+        }
+    }
+
+    writeln!(out, "")
+}
+
+/// Runs the world without a window. Commands are read line by line from the script file or
+/// stdin, the world is printed after each command. Empty lines and lines starting with `#` are
+/// ignored. The game is written to the `record` file as a replay when the input ends. Returns the
+/// exit code.
+pub fn run(script: Option<&str>, record: Option<&str>) -> i32 {
+    let input: Box<BufRead> = match script {
+        Some(path) => match File::open(path) {
+            Ok(f) => Box::new(BufReader::new(f)),
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "Can't open {}: {}", path, e);
+                return 1;
+            },
+        },
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let mut world = world::World::default();
+
+    {
+        // show the whole level
+        let (sx, sy, _) = world.map().lock().unwrap().size();
+        world.render_view().lock().unwrap().size = (sx, sy, 1);
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    world.tick();
+    world.wait();
+    print_view(&mut out, &world).unwrap();
+
+    if record.is_some() {
+        world.start_recording(replay::MapSource::Default);
+    }
+
+    for (i, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "Can't read the input: {}", e);
+                return 1;
+            },
+        };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line == "quit" {
+            break;
+        }
+
+        let cmd = match parse_command(line) {
+            Some(c) => c,
+            None => {
+                let _ = writeln!(io::stderr(), "Unknown command at line {}: '{}'", i + 1, line);
+                return 1;
+            },
+        };
+
+        world.send_player_command(cmd);
+        world.tick();
+        world.wait();
+
+        for r in world.command_reports() {
+            if let Err(e) = r.result {
+                writeln!(out, "{:?}: {}", r.command, e).unwrap();
+            }
+        }
+
+        for e in world.combat_events() {
+            writeln!(out, "{}", e).unwrap();
+        }
+
+        print_view(&mut out, &world).unwrap();
+    }
+
+    if let (Some(path), Some(r)) = (record, world.stop_recording()) {
+        if let Err(e) = r.save(path) {
+            let _ = writeln!(io::stderr(), "Can't write the replay {}: {:?}", path, e);
+            return 1;
+        }
+    }
+
+    0
+}
+
+/// Replays a game recorded with `run` and prints the final state. Returns the exit code, which
+/// isn't 0 if the game diverged from the recording.
+pub fn replay(path: &str) -> i32 {
+    let r = match replay::Replay::load(path) {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = writeln!(io::stderr(), "Can't load the replay {}: {:?}", path, e);
+            return 1;
+        },
+    };
+
+    match r.play() {
+        Ok(mut world) => {
+            {
+                let (sx, sy, _) = world.map().lock().unwrap().size();
+                world.render_view().lock().unwrap().size = (sx, sy, 1);
+            }
+            // render the final state with the new size of the view
+            world.tick();
+            world.wait();
+            print_view(&mut io::stdout(), &world).unwrap();
+            0
+        },
+        Err(e) => {
+            let _ = writeln!(io::stderr(), "The replay failed: {:?}", e);
+            1
+        },
+    }
+}
@@ -1,7 +1,48 @@
 extern crate ui;
+extern crate term_ui;
+extern crate world;
 extern crate env_logger;
 
+mod headless;
+
+use std::io::{self, Write};
+
 fn main() {
     env_logger::init().unwrap();
-    ui::start();
+
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|s| s.as_ref()) {
+        Some("--headless") => {
+            // --headless [script] [--record replay]
+            let rest: Vec<&str> = args[2..].iter().map(|s| s.as_ref()).collect();
+            let (script, record) = match rest.iter().position(|a| *a == "--record") {
+                Some(i) => (rest[..i].first().cloned(), rest.get(i + 1).cloned()),
+                None => (rest.first().cloned(), None),
+            };
+            let code = headless::run(script, record);
+            std::process::exit(code);
+        },
+        Some("--replay") => {
+            let code = match args.get(2) {
+                Some(path) => headless::replay(path),
+                None => {
+                    let _ = writeln!(io::stderr(), "Usage: --replay <file>");
+                    1
+                },
+            };
+            std::process::exit(code);
+        },
+        Some("--term") => {
+            if let Err(e) = term_ui::start() {
+                let _ = writeln!(io::stderr(), "Can't run in the terminal: {:?}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            if let Err(e) = ui::start() {
+                let _ = writeln!(io::stderr(), "Can't start the game: {:?}", e);
+                std::process::exit(1);
+            }
+        },
+    }
 }
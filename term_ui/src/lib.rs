@@ -0,0 +1,393 @@
+extern crate termion;
+#[macro_use]
+extern crate log;
+extern crate world;
+extern crate cfg;
+
+mod screen;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use cfg::CfgError;
+use cfg::ui::{KeyCfg, KeymapCfg};
+use world::cp437;
+use world::tile::{Effect, Tile};
+
+/// Delay between frames.
+const FRAME_TIME_MS: u64 = 16;
+
+/// Monsters are drawn with their glyph in this colour.
+const MONSTER_COLOR: [u8; 3] = [255, 60, 60];
+
+/// Remembered tiles out of the player's sight are drawn darker.
+const OUT_OF_SIGHT_BRIGHTNESS: f32 = 0.3;
+
+fn dim(c: [u8; 3]) -> [u8; 3] {
+    let f = |v: u8| (v as f32 * OUT_OF_SIGHT_BRIGHTNESS) as u8;
+    [f(c[0]), f(c[1]), f(c[2])]
+}
+
+fn rgb(c: [u8; 4]) -> [u8; 3] {
+    [c[0], c[1], c[2]]
+}
+
+fn to_cell(t: &Tile, terrain: &world::terrain::TerrainRegistry, entity: &cfg::ui::UiMapTileCfg) -> screen::Cell {
+    if !t.explored {
+        return screen::Cell::default();
+    }
+
+    let mark = t.effects.as_ref().and_then(|fx| fx.iter().filter_map(|e| match *e {
+        Effect::Marked(m) => Some(m),
+        _ => None,
+    }).next());
+
+    let ground = terrain.get(t.ground);
+    let (n, fg, bg) = match mark {
+        Some(world::components::PLAYER_MARK) => (entity.tile_n as u8, rgb(entity.fg), rgb(ground.bg)),
+        Some(m) => (m, MONSTER_COLOR, rgb(ground.bg)),
+        None => (t.ground, rgb(ground.fg), rgb(ground.bg)),
+    };
+
+    let (fg, bg) = if t.visible { (fg, bg) } else { (dim(fg), dim(bg)) };
+
+    screen::Cell {
+        ch: cp437::to_char(n),
+        fg: fg,
+        bg: bg,
+    }
+}
+
+/// What a key press means for the player.
+#[derive(Clone, Copy)]
+enum KeyAction {
+    Command(world::PlayerCommand),
+    /// The command needs a direction, which is given with the next key.
+    NeedsDirection(fn(world::Direction) -> world::PlayerCommand),
+    /// The command needs an item, which is chosen by its letter with the next key.
+    NeedsItem(ItemPrompt),
+    Quit,
+}
+
+#[derive(Clone, Copy)]
+enum ItemPrompt {
+    /// Use the item, or equip it if it can be equipped.
+    Use,
+    Drop,
+}
+
+impl ItemPrompt {
+    fn command(&self, idx: usize, items: &[world::components::Item]) -> Option<world::PlayerCommand> {
+        use world::PlayerCommand;
+
+        items.get(idx).map(|item| match *self {
+            ItemPrompt::Use if item.is_equippable() => PlayerCommand::Equip(idx),
+            ItemPrompt::Use => PlayerCommand::Use(idx),
+            ItemPrompt::Drop => PlayerCommand::Drop(idx),
+        })
+    }
+
+    /// Lists the items with their letters, e.g. "Drop which item? a) dagger b) potion x2".
+    fn text(&self, items: &[world::components::Item]) -> String {
+        let mut s = match *self {
+            ItemPrompt::Use => "Use or equip which item?".to_owned(),
+            ItemPrompt::Drop => "Drop which item?".to_owned(),
+        };
+
+        if items.is_empty() {
+            s.push_str(" You carry nothing.");
+        }
+
+        for (i, item) in items.iter().enumerate().take(26) {
+            let equipped = if item.equipped { " (equipped)" } else { "" };
+            s.push_str(&format!(" {}) {}{}", (b'a' + i as u8) as char, item.describe(), equipped));
+        }
+        s
+    }
+}
+
+/// Returns what the action of the keymap does in the terminal, or `None` if the action is
+/// available only in the graphical frontend.
+fn parse_action(s: &str) -> Result<Option<KeyAction>, CfgError> {
+    use world::{PlayerCommand, Direction};
+
+    let a = match s {
+        "move_n" => KeyAction::Command(PlayerCommand::Move(Direction::North)),
+        "move_ne" => KeyAction::Command(PlayerCommand::Move(Direction::NorthEast)),
+        "move_e" => KeyAction::Command(PlayerCommand::Move(Direction::East)),
+        "move_se" => KeyAction::Command(PlayerCommand::Move(Direction::SouthEast)),
+        "move_s" => KeyAction::Command(PlayerCommand::Move(Direction::South)),
+        "move_sw" => KeyAction::Command(PlayerCommand::Move(Direction::SouthWest)),
+        "move_w" => KeyAction::Command(PlayerCommand::Move(Direction::West)),
+        "move_nw" => KeyAction::Command(PlayerCommand::Move(Direction::NorthWest)),
+        "wait" => KeyAction::Command(PlayerCommand::Wait),
+        "ascend" => KeyAction::Command(PlayerCommand::Ascend),
+        "descend" => KeyAction::Command(PlayerCommand::Descend),
+        "pick_up" => KeyAction::Command(PlayerCommand::PickUp),
+        "inventory" => KeyAction::NeedsItem(ItemPrompt::Use),
+        "drop" => KeyAction::NeedsItem(ItemPrompt::Drop),
+        "open" => KeyAction::NeedsDirection(PlayerCommand::Open),
+        "close" => KeyAction::NeedsDirection(PlayerCommand::Close),
+        "interact" => KeyAction::NeedsDirection(PlayerCommand::Interact),
+        "quit" => KeyAction::Quit,
+        "new_level" | "history" | "scroll_up" | "scroll_down" => return Ok(None),
+        other => return Err(CfgError::Invalid(format!("Unknown action: '{}'", other))),
+    };
+    Ok(Some(a))
+}
+
+/// Returns the terminal's key for the key of the keymap, or `None` if the terminal doesn't tell
+/// it apart. Shifted keys are the characters of the US layout, numpad keys are the digits.
+fn to_term_key(k: &KeyCfg) -> Option<Key> {
+    let name = k.name.as_str();
+
+    if name.len() == 1 {
+        let upper = name.as_bytes()[0] as char;
+        let lower = (name.as_bytes()[0] - b'A' + b'a') as char;
+        return match (k.shift, k.ctrl) {
+            (false, false) => Some(Key::Char(lower)),
+            (true, false) => Some(Key::Char(upper)),
+            (false, true) => Some(Key::Ctrl(lower)),
+            (true, true) => None,
+        };
+    }
+
+    if k.ctrl {
+        return None;
+    }
+
+    // (the character, the character with Shift)
+    let chars = match name {
+        "Key0" | "Numpad0" => Some(('0', ')')),
+        "Key1" | "Numpad1" => Some(('1', '!')),
+        "Key2" | "Numpad2" => Some(('2', '@')),
+        "Key3" | "Numpad3" => Some(('3', '#')),
+        "Key4" | "Numpad4" => Some(('4', '$')),
+        "Key5" | "Numpad5" => Some(('5', '%')),
+        "Key6" | "Numpad6" => Some(('6', '^')),
+        "Key7" | "Numpad7" => Some(('7', '&')),
+        "Key8" | "Numpad8" => Some(('8', '*')),
+        "Key9" | "Numpad9" => Some(('9', '(')),
+        "Return" => Some(('\n', '\n')),
+        "Space" => Some((' ', ' ')),
+        "Tab" => Some(('\t', '\t')),
+        "Period" => Some(('.', '>')),
+        "Comma" => Some((',', '<')),
+        "Slash" => Some(('/', '?')),
+        "Semicolon" => Some((';', ':')),
+        "Apostrophe" => Some(('\'', '"')),
+        "Minus" => Some(('-', '_')),
+        "Equals" => Some(('=', '+')),
+        "LBracket" => Some(('[', '{')),
+        "RBracket" => Some((']', '}')),
+        "Backslash" => Some(('\\', '|')),
+        "Grave" => Some(('`', '~')),
+        _ => None,
+    };
+    if let Some((c, shifted)) = chars {
+        return Some(Key::Char(if k.shift { shifted } else { c }));
+    }
+
+    if k.shift {
+        return None;
+    }
+
+    match name {
+        "Up" => Some(Key::Up),
+        "Down" => Some(Key::Down),
+        "Left" => Some(Key::Left),
+        "Right" => Some(Key::Right),
+        "PageUp" => Some(Key::PageUp),
+        "PageDown" => Some(Key::PageDown),
+        "Home" => Some(Key::Home),
+        "End" => Some(Key::End),
+        "Insert" => Some(Key::Insert),
+        "Delete" => Some(Key::Delete),
+        "Escape" => Some(Key::Esc),
+        "Back" => Some(Key::Backspace),
+        _ if name.starts_with('F') => name[1..].parse().ok().map(Key::F),
+        _ => None,
+    }
+}
+
+/// Binds the terminal's keys to the actions of the keymap, the same one the graphical frontend
+/// uses.
+fn load_keymap(c: &KeymapCfg) -> Result<HashMap<Key, KeyAction>, CfgError> {
+    let mut keys = HashMap::new();
+
+    for (key, action) in try!(c.bindings()) {
+        let action = match try!(parse_action(&action)) {
+            Some(a) => a,
+            None => continue,
+        };
+
+        match to_term_key(&key) {
+            Some(k) => {
+                keys.insert(k, action);
+            },
+            None => warn!("the key {:?} can't be used in the terminal", key),
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Returns the direction of the key, if it's bound to a move.
+fn key_direction(keymap: &HashMap<Key, KeyAction>, k: Key) -> Option<world::Direction> {
+    match keymap.get(&k) {
+        Some(&KeyAction::Command(world::PlayerCommand::Move(d))) => Some(d),
+        _ => None,
+    }
+}
+
+/// Draws a line of text at the bottom of the screen: the last message or a prompt.
+fn render_status(screen: &mut screen::Screen, status: &str, fg: [u8; 3]) {
+    let (w, h) = screen.size();
+    if h == 0 {
+        return;
+    }
+
+    let mut chars = status.chars();
+    for x in 0..w {
+        let c = screen::Cell { ch: chars.next().unwrap_or(' '), fg: fg, ..screen::Cell::default() };
+        screen.set(x, h - 1, c);
+    }
+}
+
+fn render(screen: &mut screen::Screen, world: &world::World, entity: &cfg::ui::UiMapTileCfg) {
+    if let Some(ref rendered_view) = *world.last_render().lock().unwrap() {
+        let terrain = world.terrain();
+        for (x, y, _, t) in rendered_view.iter() {
+            screen.set(x as u16, y as u16, to_cell(t, terrain, entity));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StartError {
+    /// The ui config or its key bindings are invalid.
+    Cfg(String),
+    IO(String),
+}
+
+impl From<CfgError> for StartError {
+    fn from(e: CfgError) -> Self {
+        StartError::Cfg(format!("{:?}", e))
+    }
+}
+
+impl From<io::Error> for StartError {
+    fn from(e: io::Error) -> Self {
+        StartError::IO(format!("{:?}", e))
+    }
+}
+
+/// Runs the game in the terminal with the keys of the ui config. Fails if the config is invalid
+/// or the output is not a terminal.
+pub fn start() -> Result<(), StartError> {
+    let cfg = try!(cfg::ui::load("assets/ui.json"));
+    let dwarf_cfg = match cfg.map.tiles.get("dwarf") {
+        Some(c) => *c,
+        None => return Err(StartError::Cfg("The ui config has no 'dwarf' tile".to_owned())),
+    };
+    let keymap = try!(load_keymap(&cfg.keymap));
+
+    let mut world = world::World::default();
+
+    let stdout = io::stdout();
+    let mut out = try!(stdout.lock().into_raw_mode());
+    try!(write!(out, "{}{}", termion::clear::All, termion::cursor::Hide));
+
+    let result = run(&mut out, &mut world, &keymap, &dwarf_cfg);
+
+    // the terminal is restored even if the game failed
+    let restored = write!(out, "{}{}{}", termion::style::Reset, termion::clear::All, termion::cursor::Show)
+        .and_then(|_| out.flush());
+    try!(result);
+    try!(restored);
+    Ok(())
+}
+
+/// Runs the game loop until the player quits.
+fn run<W: Write>(out: &mut W, world: &mut world::World, keymap: &HashMap<Key, KeyAction>,
+                 dwarf_cfg: &cfg::ui::UiMapTileCfg) -> Result<(), StartError> {
+    let mut screen = screen::Screen::new(try!(termion::terminal_size()));
+    let mut keys = termion::async_stdin().keys();
+
+    let mut pending: Option<fn(world::Direction) -> world::PlayerCommand> = None;
+    let mut item_prompt: Option<ItemPrompt> = None;
+
+    loop {
+        while let Some(k) = keys.next() {
+            match try!(k) {
+                Key::Esc if pending.is_some() || item_prompt.is_some() => {
+                    pending = None;
+                    item_prompt = None;
+                },
+                k => {
+                    if let Some(f) = pending.take() {
+                        if let Some(d) = key_direction(keymap, k) {
+                            world.send_player_command(f(d));
+                        }
+                        continue;
+                    }
+
+                    if let Some(prompt) = item_prompt.take() {
+                        if let Key::Char(c @ 'a'...'z') = k {
+                            let idx = (c as u8 - b'a') as usize;
+                            if let Some(cmd) = prompt.command(idx, &world.player_inventory()) {
+                                world.send_player_command(cmd);
+                            }
+                        }
+                        continue;
+                    }
+
+                    match keymap.get(&k).cloned() {
+                        Some(KeyAction::Command(cmd)) => world.send_player_command(cmd),
+                        Some(KeyAction::NeedsDirection(f)) => pending = Some(f),
+                        Some(KeyAction::NeedsItem(p)) => item_prompt = Some(p),
+                        Some(KeyAction::Quit) => return Ok(()),
+                        None => (),
+                    }
+                },
+            }
+        }
+
+        let size = try!(termion::terminal_size());
+        if size != screen.size() {
+            screen = screen::Screen::new(size);
+            try!(write!(out, "{}", termion::clear::All));
+        }
+
+        {
+            let view = &mut world.render_view().lock().unwrap();
+            // the last line is used for the status
+            view.size = (size.0 as u32, size.1.saturating_sub(1) as u32, 1);
+        }
+
+        world.tick();
+        world.wait();
+
+        // the results are shown in the message log
+        world.command_reports();
+        world.combat_events();
+
+        render(&mut screen, world, dwarf_cfg);
+        let (status, fg) = if pending.is_some() {
+            ("Which direction?".to_owned(), [255, 255, 255])
+        } else if let Some(p) = item_prompt {
+            (p.text(&world.player_inventory()), [255, 255, 255])
+        } else {
+            let log = world.message_log().lock().unwrap();
+            log.recent(1, 0).first().map_or((String::new(), [255, 255, 255]), |m| (m.full_text(), rgb(m.color)))
+        };
+        render_status(&mut screen, &status, fg);
+        try!(screen.flush(out));
+
+        thread::sleep(Duration::from_millis(FRAME_TIME_MS));
+    }
+}
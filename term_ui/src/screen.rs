@@ -0,0 +1,80 @@
+use std::io::{self, Write};
+use termion::{color, cursor};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: [u8; 3],
+    pub bg: [u8; 3],
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            ch: ' ',
+            fg: [255, 255, 255],
+            bg: [0, 0, 0],
+        }
+    }
+}
+
+/// A double-buffered terminal screen. Cells are drawn into the back buffer, `flush` writes
+/// only the cells which differ from what's already on the terminal.
+pub struct Screen {
+    size: (u16, u16),
+    front: Vec<Option<Cell>>,
+    back: Vec<Cell>,
+}
+
+impl Screen {
+    pub fn new(size: (u16, u16)) -> Self {
+        let cnt = size.0 as usize * size.1 as usize;
+        Screen {
+            size: size,
+            front: vec![None; cnt],
+            back: vec![Cell::default(); cnt],
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, c: Cell) {
+        if x >= self.size.0 || y >= self.size.1 {
+            return;
+        }
+
+        let idx = y as usize * self.size.0 as usize + x as usize;
+        self.back[idx] = c;
+    }
+
+    pub fn flush<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let (w, _) = self.size;
+        // position of the terminal's cursor, if known
+        let mut pos: Option<(u16, u16)> = None;
+
+        for (idx, c) in self.back.iter().enumerate() {
+            if self.front[idx] == Some(*c) {
+                continue;
+            }
+
+            let x = (idx % w as usize) as u16;
+            let y = (idx / w as usize) as u16;
+
+            if pos != Some((x, y)) {
+                try!(write!(out, "{}", cursor::Goto(x + 1, y + 1)));
+            }
+
+            try!(write!(out, "{}{}{}",
+                        color::Fg(color::Rgb(c.fg[0], c.fg[1], c.fg[2])),
+                        color::Bg(color::Rgb(c.bg[0], c.bg[1], c.bg[2])),
+                        c.ch));
+
+            self.front[idx] = Some(*c);
+            pos = if x + 1 < w { Some((x + 1, y)) } else { None };
+        }
+
+        out.flush()
+    }
+}
@@ -0,0 +1,117 @@
+extern crate world;
+extern crate cfg;
+extern crate specs;
+
+mod common;
+
+use world::ai::{self, Action, Behaviour, Situation};
+use world::path::{PathCost, Point};
+use world::rng::Rng;
+use world::components::{Position, PlayerControlled, Energy, BlocksMovement};
+
+/// An open 10x10 level.
+struct Open;
+
+impl PathCost for Open {
+    fn cost(&self, from: Point, to: Point) -> Option<f32> {
+        if from.2 != to.2 { None } else { Some(1.0) }
+    }
+}
+
+fn situation(player: Option<Point>, health: Option<f32>) -> Situation {
+    Situation {
+        position: (5, 5, 0),
+        map_size: (10, 10, 1),
+        player: player,
+        health: health,
+    }
+}
+
+fn ai_cfg(idle: &str) -> cfg::monsters::AiCfg {
+    cfg::monsters::AiCfg {
+        idle: idle.to_owned(),
+        chase: true,
+        flee_below: Some(0.5),
+        keep_distance: None,
+    }
+}
+
+#[test]
+fn test_behaviours_from_cfg() {
+    assert_eq!(ai::behaviours_from_cfg(&ai_cfg("wander")).unwrap(),
+               vec![Behaviour::Flee(0.5), Behaviour::Chase, Behaviour::Wander]);
+    assert!(ai::behaviours_from_cfg(&ai_cfg("dance")).is_err());
+}
+
+#[test]
+fn test_decide() {
+    let mut rng = Rng::new(1);
+    let brave = [Behaviour::Flee(0.5), Behaviour::Chase, Behaviour::Idle];
+
+    // nobody around
+    assert_eq!(ai::decide(&brave, &Open, &situation(None, Some(1.0)), &mut rng), Action::Wait);
+
+    // healthy monsters chase, wounded ones flee
+    let s = situation(Some((8, 5, 0)), Some(1.0));
+    match ai::decide(&brave, &Open, &s, &mut rng) {
+        Action::Move((x, _, _)) => assert_eq!(x, 6),
+        a => panic!("unexpected action: {:?}", a),
+    }
+    let s = situation(Some((8, 5, 0)), Some(0.2));
+    match ai::decide(&brave, &Open, &s, &mut rng) {
+        Action::Move((x, _, _)) => assert_eq!(x, 4),
+        a => panic!("unexpected action: {:?}", a),
+    }
+
+    // archers keep their distance
+    let archer = [Behaviour::KeepDistance(3), Behaviour::Idle];
+    let s = situation(Some((6, 5, 0)), None);
+    match ai::decide(&archer, &Open, &s, &mut rng) {
+        Action::Move((x, _, _)) => assert_eq!(x, 4),
+        a => panic!("unexpected action: {:?}", a),
+    }
+    let s = situation(Some((8, 5, 0)), None);
+    assert_eq!(ai::decide(&archer, &Open, &s, &mut rng), Action::Wait);
+}
+
+fn run_monster(seed: u64, turns: u32) -> Vec<(u32, u32, u32)> {
+    use specs::Join;
+
+    let mut w = common::setup_world((20, 20, 1), &[]);
+    w.set_seed(seed);
+
+    w.create_at((0, 0, 0))
+        .with(PlayerControlled::default())
+        .with(Energy::new(world::scheduler::NORMAL_SPEED))
+        .with(BlocksMovement::default())
+        .build();
+
+    w.spawn("bat", (10, 10, 0)).unwrap();
+
+    let mut trail = Vec::new();
+    for _ in 0..turns {
+        w.send_player_command(world::PlayerCommand::Wait);
+        w.tick();
+        w.wait();
+
+        let es = w.entities();
+        let (pos_es, player_es) = (es.read::<Position>(), es.read::<PlayerControlled>());
+        for e in es.entities() {
+            if player_es.get(e).is_none() {
+                let p = pos_es.get(e).unwrap();
+                trail.push((p.x, p.y, p.z));
+            }
+        }
+    }
+
+    trail
+}
+
+#[test]
+fn test_determinism() {
+    let trail = run_monster(42, 20);
+    assert_eq!(trail, run_monster(42, 20));
+
+    // the bat has wandered off
+    assert!(trail.iter().any(|&p| p != (10, 10, 0)));
+}
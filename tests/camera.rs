@@ -0,0 +1,37 @@
+extern crate world;
+
+use world::systems::camera::CameraTarget;
+
+fn view_position(w: &mut world::World) -> (u32, u32, u32) {
+    w.tick();
+    w.wait();
+    let p = w.render_view().lock().unwrap().position;
+    p
+}
+
+#[test]
+fn test_follow_player() {
+    // the player starts at (10, 10, 0)
+    let mut w = world::World::default();
+    w.render_view().lock().unwrap().size = (10, 6, 1);
+
+    // the margins are limited by the view's size
+    assert_eq!(view_position(&mut w), (5, 7, 0));
+}
+
+#[test]
+fn test_targets() {
+    let mut w = world::World::default();
+    w.render_view().lock().unwrap().size = (10, 10, 1);
+
+    w.camera().lock().unwrap().target = CameraTarget::Locked;
+    assert_eq!(view_position(&mut w), (0, 0, 0));
+
+    // clamped to the map's edges
+    w.camera().lock().unwrap().target = CameraTarget::Point(49, 49, 0);
+    assert_eq!(view_position(&mut w), (40, 40, 0));
+
+    w.camera().lock().unwrap().clamp = false;
+    w.camera().lock().unwrap().margins = (100, 100);
+    assert_eq!(view_position(&mut w), (44, 44, 0));
+}
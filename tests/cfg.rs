@@ -4,4 +4,58 @@ extern crate cfg;
 fn test_load_standard_assets() {
     cfg::assets::load_atlas("assets/atlas_ascii.json").unwrap();
     cfg::ui::load("assets/ui.json").unwrap();
+    cfg::terrain::load("assets/terrain.json").unwrap();
+    cfg::prefabs::load("assets/prefabs.json").unwrap();
+}
+
+#[test]
+fn test_keymap() {
+    use std::collections::HashMap;
+    use cfg::ui::{KeyCfg, KeymapCfg};
+
+    let key = KeyCfg::parse("Ctrl+Shift+Q").unwrap();
+    assert_eq!((key.name.as_ref(), key.shift, key.ctrl), ("Q", true, true));
+    assert!(KeyCfg::parse("Hyper+Q").is_err());
+    assert!(KeyCfg::parse("Shift+Banana").is_err());
+
+    let mut bindings = HashMap::new();
+    bindings.insert("Ctrl+S".to_owned(), "wait".to_owned());
+    let keymap = KeymapCfg { preset: "vi".to_owned(), bindings: bindings };
+
+    let resolved = keymap.bindings().unwrap();
+    assert_eq!(resolved[&KeyCfg::parse("H").unwrap()], "move_w");
+    assert_eq!(resolved[&KeyCfg::parse("Ctrl+S").unwrap()], "wait");
+
+    let keymap = KeymapCfg { preset: "emacs".to_owned(), bindings: HashMap::new() };
+    assert!(keymap.bindings().is_err());
+}
+
+#[test]
+fn test_prefab_inheritance() {
+    use cfg::prefabs::{PrefabCfg, PrefabSetCfg, ComponentsCfg};
+
+    let prefab = |name: &str, parent: Option<&str>, health: Option<i32>, glyph: Option<u8>| {
+        PrefabCfg {
+            name: name.to_owned(),
+            parent: parent.map(|p| p.to_owned()),
+            components: ComponentsCfg { health: health, glyph: glyph, ..ComponentsCfg::default() },
+        }
+    };
+
+    let set = PrefabSetCfg {
+        prefabs: vec![prefab("base", None, Some(5), Some(1)),
+                      prefab("middle", Some("base"), Some(7), None),
+                      prefab("leaf", Some("middle"), None, Some(3)),
+                      prefab("orphan", Some("nobody"), None, None),
+                      prefab("loop", Some("loop"), None, None)],
+    };
+
+    let leaf = set.resolve("leaf").unwrap();
+    assert_eq!((leaf.health, leaf.glyph), (Some(7), Some(3)));
+    let middle = set.resolve("middle").unwrap();
+    assert_eq!((middle.health, middle.glyph), (Some(7), Some(1)));
+
+    assert!(set.resolve("orphan").is_err());
+    assert!(set.resolve("loop").is_err());
+    assert!(set.resolve("missing").is_err());
 }
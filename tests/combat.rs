@@ -0,0 +1,75 @@
+extern crate world;
+extern crate specs;
+
+mod common;
+
+use world::{PlayerCommand, Direction, CombatEvent, CombatOutcome};
+use world::components::{Position, PlayerControlled, BlocksMovement, Health, CombatStats, Name, Energy,
+                        CORPSE_MARK};
+
+fn event(attacker: &str, target: &str, outcome: CombatOutcome) -> CombatEvent {
+    CombatEvent {
+        attacker: attacker.to_owned(),
+        target: target.to_owned(),
+        outcome: outcome,
+    }
+}
+
+#[test]
+fn test_event_text() {
+    assert_eq!(event("you", "rat", CombatOutcome::Hit(3)).to_string(), "You hit rat for 3.");
+    assert_eq!(event("rat", "you", CombatOutcome::Miss).to_string(), "Rat misses you.");
+    assert_eq!(event("goblin", "rat", CombatOutcome::Kill(5)).to_string(), "Goblin kills rat!");
+}
+
+#[test]
+fn test_bump_to_attack() {
+    let mut w = common::setup_world((5, 5, 1), &[]);
+
+    w.create_at((1, 1, 0))
+        .with(PlayerControlled::default())
+        .with(BlocksMovement::default())
+        .with(CombatStats::new(3, 0, 0.9))
+        .with(Name::new("you"))
+        // attacks are rolled once per turn, turns pass only for actors with energy
+        .with(Energy::new(world::scheduler::NORMAL_SPEED))
+        .build();
+
+    let dummy = w.create_at((2, 1, 0))
+        .with(BlocksMovement::default())
+        .with(Health::new(10))
+        .with(Name::new("dummy"))
+        .build();
+
+    let mut events = Vec::new();
+    for _ in 0..100 {
+        w.send_player_command(PlayerCommand::Move(Direction::East));
+        w.tick();
+        events.extend(w.combat_events());
+
+        if let Some(&CombatEvent { outcome: CombatOutcome::Kill(_), .. }) = events.last() {
+            break;
+        }
+    }
+
+    // every bump was an attack on the dummy
+    let total: i32 = events.iter().map(|e| match e.outcome {
+        CombatOutcome::Hit(d) | CombatOutcome::Kill(d) => d,
+        CombatOutcome::Miss => 0,
+    }).sum();
+    assert!(total >= 10);
+    assert!(events.iter().all(|e| e.attacker == "you" && e.target == "dummy"));
+
+    // the dummy is replaced with its corpse
+    w.wait();
+
+    let es = w.entities();
+    let (pos_es, vis_es, name_es) = (es.read::<Position>(), es.read::<world::components::Visible>(),
+                                     es.read::<Name>());
+    assert!(pos_es.get(dummy).is_none());
+
+    let corpse = es.entities().find(|e| name_es.get(*e).map_or(false, |n| n.name == "dummy corpse")).unwrap();
+    let p = pos_es.get(corpse).unwrap();
+    assert_eq!((p.x, p.y, p.z), (2, 1, 0));
+    assert_eq!(vis_es.get(corpse).unwrap().mark, CORPSE_MARK);
+}
@@ -0,0 +1,37 @@
+//! Fixtures shared by the tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use world::{self, PlayerCommand, CommandError};
+use world::components::PlayerControlled;
+use world::terrain::TerrainRegistry;
+
+/// Creates a world without entities which spawns the prefabs of the assets. The map is covered
+/// with floor, except for the cells with the named terrain.
+pub fn setup_world(size: (u32, u32, u32), cells: &[((u32, u32, u32), &str)]) -> world::World {
+    let terrain = Arc::new(TerrainRegistry::load("assets/terrain.json").unwrap());
+    let mut map = world::map::Map::new(size, terrain.cell_of("floor").unwrap());
+    for &(p, name) in cells {
+        map[p] = terrain.cell_of(name).unwrap();
+    }
+
+    let mut w = world::World::new(map, terrain);
+    w.load_prefabs("assets/prefabs.json").unwrap();
+    w
+}
+
+/// Starts building the player's entity at `(2, 2, 0)`, other components are added by the test.
+pub fn player(w: &world::World) -> world::PlacedEntityBuilder {
+    w.create_at((2, 2, 0))
+        .with(PlayerControlled::default())
+}
+
+/// Performs the command and returns its result.
+pub fn perform(w: &mut world::World, cmd: PlayerCommand) -> Result<(), CommandError> {
+    w.send_player_command(cmd);
+    w.tick();
+    let reports = w.command_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].command, cmd);
+    reports[0].result
+}
@@ -0,0 +1,72 @@
+extern crate world;
+
+mod common;
+
+use std::collections::HashSet;
+use world::{fov, gen, map};
+use world::components::{PlayerControlled, Viewer};
+
+fn compute(rows: &[&str], origin: (u32, u32), radius: u32) -> HashSet<(u32, u32)> {
+    let size = (rows[0].len() as u32, rows.len() as u32);
+    let blocks = |x: u32, y: u32| rows[y as usize].as_bytes()[x as usize] == b'#';
+
+    let mut visible = HashSet::new();
+    fov::compute(origin, radius, size, blocks, |x, y| { visible.insert((x, y)); });
+    visible
+}
+
+#[test]
+fn test_walls_block_sight() {
+    let rows = [
+        "#######",
+        "#..#..#",
+        "#..#..#",
+        "#######",
+    ];
+
+    let v = compute(&rows, (1, 1), 10);
+    assert!(v.contains(&(1, 1)));
+    assert!(v.contains(&(2, 2)));
+
+    // the wall itself is visible, but not what's behind it
+    assert!(v.contains(&(3, 1)));
+    assert!(!v.contains(&(4, 1)));
+    assert!(!v.contains(&(5, 2)));
+}
+
+#[test]
+fn test_radius() {
+    let rows = [
+        "..........",
+        "..........",
+    ];
+
+    let v = compute(&rows, (0, 0), 3);
+    assert!(v.contains(&(3, 0)));
+    assert!(!v.contains(&(4, 0)));
+}
+
+#[test]
+fn test_memory_survives_level_changes() {
+    let mut w = common::setup_world((5, 5, 1), &[]);
+    w.create_at((2, 2, 0))
+        .with(PlayerControlled::default())
+        .with(Viewer::new(2))
+        .build();
+    w.tick();
+    w.wait();
+    assert!(w.memory().lock().unwrap().get((2, 2, 0)).is_some());
+
+    let floor = w.terrain().cell_of("floor").unwrap();
+    let level = |size| gen::Level { map: map::Map::new(size, floor), rooms: Vec::new(), spawn: (0, 0, 0) };
+
+    // a new level is unexplored
+    w.set_level("second", level((8, 8, 1)));
+    assert_eq!(w.level(), "second");
+    assert_eq!(w.memory().lock().unwrap().size(), (8, 8, 1));
+    assert!(w.memory().lock().unwrap().get((2, 2, 0)).is_none());
+
+    // the first one is remembered when the player returns
+    w.set_level("", level((5, 5, 1)));
+    assert!(w.memory().lock().unwrap().get((2, 2, 0)).is_some());
+}
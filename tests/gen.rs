@@ -0,0 +1,102 @@
+extern crate world;
+
+use world::gen::{self, Generator, Tiles};
+
+const TILES: Tiles = Tiles { floor: 46, wall: 219 };
+const SIZE: (u32, u32, u32) = (50, 30, 2);
+
+fn generators(seed: u64) -> Vec<Box<Generator>> {
+    vec![
+        Box::new(gen::bsp::BspGenerator::new(seed, TILES)),
+        Box::new(gen::cave::CaveGenerator::new(seed, TILES)),
+        Box::new(gen::drunkard::DrunkardGenerator::new(seed, TILES)),
+    ]
+}
+
+fn same_maps(a: &gen::Level, b: &gen::Level) -> bool {
+    let (sx, sy, sz) = a.map.size();
+    (0..sz).all(|z| (0..sy).all(|y| (0..sx).all(|x| a.map[(x, y, z)] == b.map[(x, y, z)])))
+}
+
+#[test]
+fn test_same_seed_same_level() {
+    for (g1, g2) in generators(42).iter().zip(generators(42).iter()) {
+        let a = g1.generate(SIZE).unwrap();
+        let b = g2.generate(SIZE).unwrap();
+
+        assert!(same_maps(&a, &b));
+        assert_eq!(a.rooms, b.rooms);
+        assert_eq!(a.spawn, b.spawn);
+    }
+}
+
+#[test]
+fn test_different_seeds() {
+    for (g1, g2) in generators(1).iter().zip(generators(2).iter()) {
+        assert!(!same_maps(&g1.generate(SIZE).unwrap(), &g2.generate(SIZE).unwrap()));
+    }
+}
+
+#[test]
+fn test_adjacent_seeds() {
+    // the seeds differ only in the lowest bit
+    for (g1, g2) in generators(42).iter().zip(generators(43).iter()) {
+        assert!(!same_maps(&g1.generate(SIZE).unwrap(), &g2.generate(SIZE).unwrap()));
+    }
+}
+
+#[test]
+fn test_invalid_sizes() {
+    for g in generators(7) {
+        assert!(g.generate((2, 10, 1)).is_err());
+        assert!(g.generate((10, 2, 1)).is_err());
+        assert!(g.generate((10, 10, 0)).is_err());
+
+        let l = g.generate((3, 3, 1)).unwrap();
+        assert_eq!(l.map[l.spawn], TILES.floor);
+    }
+}
+
+#[test]
+fn test_spawn_is_open() {
+    for g in generators(7) {
+        let l = g.generate(SIZE).unwrap();
+        assert_eq!(l.map.size(), SIZE);
+        assert_eq!(l.map[l.spawn], TILES.floor);
+    }
+}
+
+#[test]
+fn test_bsp_rooms() {
+    let l = gen::bsp::BspGenerator::new(3, TILES).generate(SIZE).unwrap();
+    assert!(!l.rooms.is_empty());
+
+    for r in &l.rooms {
+        let (x, y, z) = r.center();
+        assert_eq!(l.map[(x, y, z)], TILES.floor);
+    }
+}
+
+#[test]
+fn test_stacked_levels_are_connected() {
+    let terrain = world::terrain::TerrainRegistry::load("assets/terrain.json").unwrap();
+    let (sx, sy, _) = SIZE;
+
+    let levels = generators(5).iter().map(|g| g.generate((sx, sy, 1)).unwrap()).collect();
+    let mut l = gen::stack(levels).unwrap();
+    assert_eq!(l.map.size(), (sx, sy, 3));
+    assert!(gen::connect_levels(&mut l, &terrain, 5));
+
+    let (up, down) = (terrain.cell_of("stairs up").unwrap(), terrain.cell_of("stairs down").unwrap());
+    for z in 1..3 {
+        // every staircase down leads to a staircase up
+        let stairs: Vec<(u32, u32)> = (0..sy)
+            .flat_map(|y| (0..sx).map(move |x| (x, y)))
+            .filter(|&(x, y)| l.map[(x, y, z - 1)] == down)
+            .collect();
+        assert_eq!(stairs.len(), 1);
+
+        let (x, y) = stairs[0];
+        assert_eq!(l.map[(x, y, z)], up);
+    }
+}
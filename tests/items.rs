@@ -0,0 +1,154 @@
+extern crate world;
+extern crate specs;
+
+mod common;
+
+use specs::Join;
+use world::{PlayerCommand, CommandError};
+use world::components::{Position, BlocksMovement, Visible, Health, Inventory, Item, PlayerControlled, Dead,
+                        PLAYER_MARK};
+use world::tile::Effect;
+use common::perform;
+
+fn setup_world(max_weight: f32) -> world::World {
+    let w = common::setup_world((5, 5, 1), &[]);
+    let mut health = Health::new(20);
+    health.current = 5;
+
+    common::player(&w)
+        .with(BlocksMovement::default())
+        .with(Visible::default())
+        .with(health)
+        .with(Inventory::new(max_weight))
+        .build();
+    w
+}
+
+fn floor_items(w: &world::World) -> Vec<(String, (u32, u32, u32))> {
+    let e = w.entities();
+    let (pos_es, item_es) = (e.read::<Position>(), e.read::<Item>());
+    (&pos_es, &item_es).iter().map(|(p, i)| (i.name.clone(), (p.x, p.y, p.z))).collect()
+}
+
+#[test]
+fn test_pick_up_use_and_drop() {
+    let mut w = setup_world(50.0);
+    w.spawn("healing potion", (2, 2, 0)).unwrap();
+    w.spawn("healing potion", (2, 2, 0)).unwrap();
+    w.spawn("dagger", (3, 2, 0)).unwrap();
+
+    // potions are carried in a single stack
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Err(CommandError::NothingToPickUp));
+
+    let inventory = w.player_inventory();
+    assert_eq!(inventory.len(), 1);
+    assert_eq!(inventory[0].count, 2);
+
+    assert_eq!(perform(&mut w, PlayerCommand::Use(0)), Ok(()));
+    assert_eq!(w.player_inventory()[0].count, 1);
+    {
+        let e = w.entities();
+        let health_es = e.read::<Health>();
+        assert_eq!((&health_es).iter().next().unwrap().current, 15);
+    }
+
+    assert_eq!(perform(&mut w, PlayerCommand::Drop(1)), Err(CommandError::NoSuchItem));
+    assert_eq!(perform(&mut w, PlayerCommand::Drop(0)), Ok(()));
+    assert!(w.player_inventory().is_empty());
+
+    let mut items = floor_items(&w);
+    items.sort();
+    assert_eq!(items, vec![("dagger".to_owned(), (3, 2, 0)), ("healing potion".to_owned(), (2, 2, 0))]);
+}
+
+#[test]
+fn test_equip() {
+    let mut w = setup_world(50.0);
+    w.spawn("dagger", (2, 2, 0)).unwrap();
+    w.spawn("short sword", (2, 2, 0)).unwrap();
+    w.spawn("gold coin", (2, 2, 0)).unwrap();
+    for _ in 0..3 {
+        assert_eq!(perform(&mut w, PlayerCommand::PickUp), Ok(()));
+    }
+
+    let idx = |w: &world::World, name: &str| w.player_inventory().iter().position(|i| i.name == name).unwrap();
+    let equipped = |w: &world::World| -> Vec<String> {
+        w.player_inventory().into_iter().filter(|i| i.equipped).map(|i| i.name).collect()
+    };
+
+    let (dagger, sword, coin) = (idx(&w, "dagger"), idx(&w, "short sword"), idx(&w, "gold coin"));
+
+    assert_eq!(perform(&mut w, PlayerCommand::Equip(dagger)), Ok(()));
+    assert_eq!(equipped(&w), vec!["dagger".to_owned()]);
+
+    // only one weapon at a time
+    assert_eq!(perform(&mut w, PlayerCommand::Equip(sword)), Ok(()));
+    assert_eq!(equipped(&w), vec!["short sword".to_owned()]);
+
+    // equipping again takes the item off
+    assert_eq!(perform(&mut w, PlayerCommand::Equip(sword)), Ok(()));
+    assert!(equipped(&w).is_empty());
+
+    assert_eq!(perform(&mut w, PlayerCommand::Equip(coin)), Err(CommandError::CantEquip));
+    assert_eq!(perform(&mut w, PlayerCommand::Use(dagger)), Err(CommandError::CantUse));
+}
+
+#[test]
+fn test_too_heavy() {
+    let mut w = setup_world(5.0);
+    w.spawn("leather armor", (2, 2, 0)).unwrap();
+
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Err(CommandError::TooHeavy));
+    assert_eq!(floor_items(&w).len(), 1);
+}
+
+#[test]
+fn test_dead_monsters_drop_items() {
+    let mut w = setup_world(50.0);
+    w.spawn("dagger", (2, 2, 0)).unwrap();
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::Equip(0)), Ok(()));
+    assert!(floor_items(&w).is_empty());
+
+    // the player's entity becomes a monster and dies
+    {
+        let e = w.entities();
+        let (mut player_es, mut dead_es) = (e.write::<PlayerControlled>(), e.write::<Dead>());
+        let player = e.entities().find(|x| player_es.get(*x).is_some()).unwrap();
+        player_es.remove(player);
+        dead_es.insert(player, Dead);
+    }
+    w.tick();
+    w.wait();
+
+    assert_eq!(floor_items(&w), vec![("dagger".to_owned(), (2, 2, 0))]);
+    let index = w.spatial_index().lock().unwrap();
+    assert_eq!(index.at((2, 2, 0)).len(), 2);
+}
+
+#[test]
+fn test_items_are_drawn_below_actors() {
+    let mut w = setup_world(50.0);
+    w.spawn("dagger", (2, 2, 0)).unwrap();
+    w.spawn("gold coin", (1, 1, 0)).unwrap();
+
+    w.tick();
+    w.wait();
+
+    let render = w.last_render().lock().unwrap();
+    let top_mark = |x, y| {
+        render.as_ref().unwrap().iter()
+            .find(|&(tx, ty, _, _)| (tx, ty) == (x, y))
+            .and_then(|(_, _, _, t)| t.effects.as_ref())
+            .and_then(|fx| fx.iter().filter_map(|e| match *e {
+                Effect::Marked(m) => Some(m),
+                _ => None,
+            }).next())
+    };
+
+    assert_eq!(top_mark(2, 2), Some(PLAYER_MARK));
+    let coin = w.prefabs().resolve("gold coin").unwrap().item.unwrap();
+    assert_eq!(top_mark(1, 1), Some(coin.glyph));
+}
@@ -0,0 +1,48 @@
+extern crate world;
+
+mod common;
+
+use world::components::LightSource;
+use world::light::{Lighting, WHITE};
+
+#[test]
+fn test_ambient_light() {
+    let mut l = Lighting::default();
+    assert_eq!(l.light_at((0, 0, 0)), WHITE);
+
+    l.set_ambient(2, [0.1, 0.1, 0.2]);
+    assert_eq!(l.light_at((0, 0, 2)), [0.1, 0.1, 0.2]);
+    assert_eq!(l.light_at((0, 0, 1)), WHITE);
+
+    l.add((0, 0, 2), [0.5, 0.0, 0.0]);
+    assert_eq!(l.light_at((0, 0, 2)), [0.6, 0.1, 0.2]);
+
+    l.clear();
+    assert_eq!(l.light_at((0, 0, 2)), [0.1, 0.1, 0.2]);
+}
+
+#[test]
+fn test_light_is_blocked_by_walls() {
+    let mut w = common::setup_world((7, 3, 1), &[((3, 0, 0), "wall"), ((3, 1, 0), "wall"), ((3, 2, 0), "wall")]);
+    w.lighting().lock().unwrap().default_ambient = [0.0, 0.0, 0.0];
+    w.create_at((1, 1, 0))
+        .with(LightSource::new(5, [1.0, 0.5, 0.0], 1.0, 0.0))
+        .build();
+
+    w.tick();
+    w.wait();
+
+    let render = w.last_render().lock().unwrap();
+    let light = |x, y| {
+        render.as_ref().unwrap().iter()
+            .find(|&(tx, ty, _, _)| (tx, ty) == (x, y))
+            .and_then(|(_, _, _, t)| t.light())
+            .unwrap()
+    };
+
+    let near = light(2, 1);
+    assert!(near[0] > 0.0 && near[1] > 0.0 && near[2] == 0.0);
+    // the light fades with the distance
+    assert!(near[0] > light(2, 2)[0]);
+    assert_eq!(light(5, 1), [0.0, 0.0, 0.0]);
+}
@@ -0,0 +1,91 @@
+extern crate world;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use world::map;
+
+/// Writes the data to a file in the temporary directory. The name gets a timestamp, so tests
+/// running at the same time don't overwrite each other's files.
+fn write_tmp(name: &str, data: &str) -> String {
+    let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let p = env::temp_dir().join(format!("{}_{}_{}", t.as_secs(), t.subsec_nanos(), name));
+    File::create(&p).unwrap().write_all(data.as_bytes()).unwrap();
+    p.to_str().unwrap().to_owned()
+}
+
+#[test]
+fn test_load_tmx() {
+    let m = map::load_from_tmx("assets/test.tmx").unwrap();
+    assert_eq!(m.size(), (50, 50, 1));
+
+    // the TMX file contains the same data as the CSV one, offset by the tileset's firstgid
+    let csv = map::load_from_csv("assets/test_map.csv", (50, 50, 1)).unwrap();
+    for y in 0..50 {
+        for x in 0..50 {
+            assert_eq!(m[(x, y, 0)], csv[(x, y, 0)]);
+        }
+    }
+}
+
+#[test]
+fn test_load_tmx_layers() {
+    let m = map::load_from_tmx("assets/test_layers.tmx").unwrap();
+    assert_eq!(m.size(), (4, 3, 3));
+
+    // csv
+    assert_eq!(m[(0, 0, 0)], 46);
+    assert_eq!(m[(1, 1, 0)], 205);
+
+    // base64 + zlib
+    assert_eq!(m[(0, 0, 1)], 0);
+    assert_eq!(m[(1, 0, 1)], 1);
+    assert_eq!(m[(3, 1, 1)], 1);
+
+    // base64, flipped tiles keep their ids
+    assert_eq!(m[(0, 0, 2)], 0);
+    assert_eq!(m[(3, 2, 2)], 0);
+}
+
+#[test]
+fn test_load_csv_trailing_commas_and_blank_lines() {
+    let p = write_tmp("rogue_map_ok.csv", "1,2,3,\n\n4,5,6\n\n");
+    let m = map::load_from_csv(&p, (3, 2, 1)).unwrap();
+    assert_eq!(m[(2, 0, 0)], 3);
+    assert_eq!(m[(0, 1, 0)], 4);
+}
+
+#[test]
+fn test_load_csv_errors() {
+    // a short row
+    let p = write_tmp("rogue_map_short_row.csv", "1,2,3\n4,5\n");
+    let e = map::load_from_csv(&p, (3, 2, 1)).unwrap_err();
+    assert_eq!((e.line, e.column), (2, 1));
+
+    // not enough rows
+    let p = write_tmp("rogue_map_short.csv", "1,2,3\n4,5,6\n");
+    assert!(map::load_from_csv(&p, (3, 3, 1)).is_err());
+
+    // an invalid value
+    let p = write_tmp("rogue_map_invalid.csv", "1,2,3\n4, x,6\n");
+    let e = map::load_from_csv(&p, (3, 2, 1)).unwrap_err();
+    assert_eq!((e.line, e.column), (2, 4));
+
+    // missing file
+    assert!(map::load_from_csv("assets/nope.csv", (1, 1, 1)).is_err());
+}
+
+#[test]
+fn test_map_memory() {
+    let mut m = map::MapMemory::new((3, 3, 2));
+    assert_eq!(m.get((1, 1, 1)), None);
+
+    m.remember((1, 1, 1), 205);
+    assert_eq!(m.get((1, 1, 1)), Some(205));
+    assert_eq!(m.get((1, 1, 0)), None);
+
+    m.reset((4, 4, 1));
+    assert_eq!(m.size(), (4, 4, 1));
+    assert_eq!(m.get((1, 1, 0)), None);
+}
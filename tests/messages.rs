@@ -0,0 +1,40 @@
+extern crate world;
+
+use world::messages::{MessageLog, Severity};
+
+#[test]
+fn test_merging() {
+    let mut log = MessageLog::new(10);
+    log.add("The rat misses you.", Severity::Info, 1);
+    log.add("The rat misses you.", Severity::Info, 2);
+    log.add("The rat misses you.", Severity::Info, 3);
+    log.add("The rat bites you.", Severity::Danger, 4);
+    log.add("The rat misses you.", Severity::Info, 5);
+
+    assert_eq!(log.len(), 3);
+
+    let first = log.get(0).unwrap();
+    assert_eq!(first.full_text(), "The rat misses you. x3");
+    assert_eq!(first.turn, 3);
+    assert_eq!(first.color, Severity::Info.color());
+
+    assert_eq!(log.get(1).unwrap().full_text(), "The rat bites you.");
+    assert_eq!(log.get(2).unwrap().count, 1);
+}
+
+#[test]
+fn test_capacity_and_scrolling() {
+    let mut log = MessageLog::new(3);
+    for i in 0..5 {
+        log.add(&i.to_string(), Severity::Info, i);
+    }
+
+    assert_eq!(log.len(), 3);
+    assert_eq!(log.get(0).unwrap().text, "2");
+
+    let texts = |skip| log.recent(2, skip).iter().map(|m| m.text.clone()).collect::<Vec<_>>();
+    assert_eq!(texts(0), vec!["3", "4"]);
+    assert_eq!(texts(1), vec!["2", "3"]);
+    assert_eq!(texts(2), vec!["2"]);
+    assert!(texts(5).is_empty());
+}
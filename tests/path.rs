@@ -0,0 +1,67 @@
+extern crate world;
+
+use world::path::{self, Neighbourhood, PathCost, Point};
+
+/// A single z-level map, `#` is a wall.
+struct Grid(Vec<&'static str>);
+
+impl Grid {
+    fn size(&self) -> (u32, u32, u32) {
+        (self.0[0].len() as u32, self.0.len() as u32, 1)
+    }
+}
+
+impl PathCost for Grid {
+    fn cost(&self, from: Point, to: Point) -> Option<f32> {
+        if from.2 != to.2 || self.0[to.1 as usize].as_bytes()[to.0 as usize] == b'#' {
+            None
+        } else {
+            Some(1.0)
+        }
+    }
+}
+
+#[test]
+fn test_find_path() {
+    let g = Grid(vec![
+        "......",
+        ".####.",
+        "......",
+    ]);
+
+    let p = path::find_path(&g, g.size(), (0, 1, 0), (5, 1, 0), Neighbourhood::Four).unwrap();
+    assert_eq!(p.len(), 7);
+    assert_eq!(p.last(), Some(&(5, 1, 0)));
+
+    let p = path::find_path(&g, g.size(), (0, 1, 0), (5, 1, 0), Neighbourhood::Eight).unwrap();
+    assert_eq!(p.len(), 5);
+}
+
+#[test]
+fn test_no_path() {
+    let g = Grid(vec![
+        "..#..",
+        "..#..",
+    ]);
+
+    assert!(path::find_path(&g, g.size(), (0, 0, 0), (4, 0, 0), Neighbourhood::Eight).is_none());
+}
+
+#[test]
+fn test_dijkstra_map() {
+    let g = Grid(vec![
+        "......",
+        ".####.",
+        "......",
+    ]);
+
+    let d = path::DijkstraMap::new(&g, g.size(), &[(0, 0, 0), (5, 2, 0)], Neighbourhood::Four);
+    assert_eq!(d.get((0, 0, 0)), Some(0.0));
+    assert_eq!(d.get((5, 0, 0)), Some(2.0));
+    assert_eq!(d.get((1, 1, 0)), None);
+    assert_eq!(d.next_step(&g, (5, 0, 0)), Some((5, 1, 0)));
+
+    // running away from the goal at (0, 0)
+    let f = d.flee(&g, -1.2);
+    assert_eq!(f.next_step(&g, (2, 0, 0)), Some((3, 0, 0)));
+}
@@ -0,0 +1,105 @@
+extern crate world;
+extern crate specs;
+
+mod common;
+
+use specs::Join;
+use world::{PlayerCommand, Direction, CommandError};
+use world::components::{Position, PlayerControlled, BlocksMovement, WantsToMove};
+use common::perform;
+
+fn setup_world() -> world::World {
+    let w = common::setup_world((5, 5, 1), &[((2, 1, 0), "door")]);
+    common::player(&w).build();
+    w
+}
+
+fn player_position(w: &world::World) -> (u32, u32, u32) {
+    let e = w.entities();
+    let (pos_es, player_es) = (e.read::<Position>(), e.read::<PlayerControlled>());
+    let p = (&pos_es, &player_es).iter().next().unwrap().0;
+    (p.x, p.y, p.z)
+}
+
+#[test]
+fn test_moves() {
+    let mut w = setup_world();
+
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::SouthEast)), Ok(()));
+    assert_eq!(player_position(&w), (3, 3, 0));
+
+    assert_eq!(perform(&mut w, PlayerCommand::Wait), Ok(()));
+    assert_eq!(player_position(&w), (3, 3, 0));
+
+    assert_eq!(perform(&mut w, PlayerCommand::Ascend), Err(CommandError::NoLevel));
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Err(CommandError::NothingToPickUp));
+}
+
+#[test]
+fn test_doors() {
+    let mut w = setup_world();
+    let (door, open_door) = (w.terrain().cell_of("door").unwrap(), w.terrain().cell_of("open door").unwrap());
+
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::North)), Err(CommandError::Blocked));
+    assert_eq!(perform(&mut w, PlayerCommand::Open(Direction::West)), Err(CommandError::NothingToOpen));
+
+    assert_eq!(perform(&mut w, PlayerCommand::Open(Direction::North)), Ok(()));
+    assert_eq!(w.map().lock().unwrap()[(2, 1, 0)], open_door);
+
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::North)), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::South)), Ok(()));
+
+    // the door can't be closed while something stands in it
+    let e = w.create_at((2, 1, 0)).build();
+    assert_eq!(perform(&mut w, PlayerCommand::Close(Direction::North)), Err(CommandError::Blocked));
+    assert_eq!(perform(&mut w, PlayerCommand::Interact(Direction::North)), Err(CommandError::Blocked));
+    w.delete(e);
+
+    assert_eq!(perform(&mut w, PlayerCommand::Interact(Direction::North)), Ok(()));
+    assert_eq!(w.map().lock().unwrap()[(2, 1, 0)], door);
+}
+
+#[test]
+fn test_shared_movement() {
+    let mut w = setup_world();
+
+    // any entity with an intent is moved, not only the player
+    let monster = w.create_at((0, 0, 0))
+        .with(BlocksMovement::default())
+        .with(WantsToMove::new(1, 1, 0))
+        .build();
+    w.tick();
+    w.wait();
+    {
+        let pos_es = w.entities().read::<Position>();
+        let p = pos_es.get(monster).unwrap();
+        assert_eq!((p.x, p.y, p.z), (1, 1, 0));
+        assert!(w.entities().read::<WantsToMove>().get(monster).is_none());
+    }
+
+    // the player can't walk into the monster
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::NorthWest)), Err(CommandError::Blocked));
+    assert_eq!(player_position(&w), (2, 2, 0));
+}
+
+#[test]
+fn test_stairs() {
+    let mut w = common::setup_world((5, 5, 2), &[((1, 1, 0), "stairs down"), ((1, 1, 1), "stairs up")]);
+    common::player(&w).build();
+
+    assert_eq!(perform(&mut w, PlayerCommand::Descend), Err(CommandError::NoStairs));
+
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::NorthWest)), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::Ascend), Err(CommandError::NoStairs));
+    assert_eq!(perform(&mut w, PlayerCommand::Descend), Ok(()));
+    assert_eq!(player_position(&w), (1, 1, 1));
+
+    // the view follows the player
+    w.tick();
+    w.wait();
+    assert_eq!(w.render_view().lock().unwrap().position.2, 1);
+
+    assert_eq!(perform(&mut w, PlayerCommand::Descend), Err(CommandError::NoLevel));
+    assert_eq!(perform(&mut w, PlayerCommand::Ascend), Ok(()));
+    assert_eq!(player_position(&w), (1, 1, 0));
+}
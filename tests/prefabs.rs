@@ -0,0 +1,53 @@
+extern crate world;
+extern crate specs;
+
+mod common;
+
+use specs::Join;
+use world::components::{Position, Name, Health, CombatStats, Ai, LightSource, Item, PlayerControlled};
+
+fn setup_world() -> world::World {
+    common::setup_world((5, 5, 1), &[])
+}
+
+#[test]
+fn test_spawn() {
+    let w = setup_world();
+    let shaman = w.spawn("goblin shaman", (1, 2, 0)).unwrap();
+
+    let e = w.entities();
+    assert_eq!(e.read::<Position>().get(shaman).map(|p| (p.x, p.y, p.z)), Some((1, 2, 0)));
+    assert_eq!(e.read::<Name>().get(shaman).map(|n| n.name.clone()), Some("goblin shaman".to_owned()));
+    // own components replace the inherited ones
+    assert_eq!(e.read::<Health>().get(shaman).map(|h| h.max), Some(7));
+    assert!(e.read::<LightSource>().get(shaman).is_some());
+    // the goblin's
+    assert_eq!(e.read::<CombatStats>().get(shaman).map(|c| (c.power, c.defense)), Some((4, 1)));
+    assert!(e.read::<Ai>().get(shaman).is_some());
+}
+
+#[test]
+fn test_spawn_item() {
+    let w = setup_world();
+    let potion = w.spawn("healing potion", (3, 3, 0)).unwrap();
+
+    let e = w.entities();
+    assert_eq!(e.read::<Item>().get(potion).map(|i| i.name.clone()), Some("healing potion".to_owned()));
+    assert!(e.read::<Health>().get(potion).is_none());
+}
+
+#[test]
+fn test_spawn_unknown() {
+    let w = setup_world();
+    assert!(w.spawn("dragon", (0, 0, 0)).is_err());
+}
+
+#[test]
+fn test_default_player() {
+    let w = world::World::default();
+
+    let e = w.entities();
+    let (player_es, health_es) = (e.read::<PlayerControlled>(), e.read::<Health>());
+    let players: Vec<i32> = (&player_es, &health_es).iter().map(|(_, h)| h.max).collect();
+    assert_eq!(players, vec![30]);
+}
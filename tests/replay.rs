@@ -0,0 +1,97 @@
+extern crate world;
+
+use std::env;
+use world::{PlayerCommand, Direction};
+use world::replay::{Replay, ReplayError, MapSource};
+
+/// Plays a game with a monster next to the player. The monster isn't in the map source.
+fn record(seed: u64) -> Replay {
+    let mut w = world::World::default();
+    w.set_seed(seed);
+    w.tick();
+
+    w.start_recording(MapSource::Default);
+    w.spawn("rat", (12, 10, 0)).unwrap();
+
+    let commands = [PlayerCommand::Move(Direction::East), PlayerCommand::Wait, PlayerCommand::Move(Direction::East),
+                    PlayerCommand::Move(Direction::South), PlayerCommand::Move(Direction::West)];
+    for _ in 0..3 {
+        for cmd in &commands {
+            w.send_player_command(*cmd);
+            w.tick();
+        }
+    }
+
+    w.stop_recording().unwrap()
+}
+
+#[test]
+fn test_record() {
+    let r = record(5);
+
+    assert_eq!(r.seed, 5);
+    assert_eq!(r.source, MapSource::Default);
+    assert_eq!(r.commands.len(), 15);
+    assert_eq!(r.commands[1].command, PlayerCommand::Wait);
+    assert!(r.commands.windows(2).all(|c| c[0].turn <= c[1].turn));
+
+    // before the first and the 11th command, and at the end
+    let at: Vec<usize> = r.checkpoints.iter().map(|c| c.command).collect();
+    assert_eq!(at, vec![0, 10, 15]);
+}
+
+#[test]
+fn test_replay_without_spawns() {
+    // the replay starts from the default world, the monsters spawned while recording aren't there
+    let r = record(5);
+
+    match r.play() {
+        Err(ReplayError::Diverged(_)) => (),
+        _ => panic!("the divergence is not detected"),
+    }
+}
+
+#[test]
+fn test_replay() {
+    let mut w = world::World::default();
+    w.set_seed(7);
+    w.tick();
+    w.start_recording(MapSource::Default);
+
+    for d in &[Direction::East, Direction::East, Direction::South, Direction::West, Direction::North] {
+        w.send_player_command(PlayerCommand::Move(*d));
+        w.tick();
+    }
+    w.send_player_command(PlayerCommand::Wait);
+    w.tick();
+
+    let r = w.stop_recording().unwrap();
+    let path = env::temp_dir().join("rogue_test.replay");
+    let path = path.to_str().unwrap();
+    r.save(path).unwrap();
+
+    let loaded = Replay::load(path).unwrap();
+    assert_eq!(loaded.commands, r.commands);
+    assert_eq!(loaded.checkpoints, r.checkpoints);
+
+    let mut replayed = loaded.play().unwrap();
+    assert_eq!(replayed.turn(), w.turn());
+    assert_eq!(replayed.state_hash(), w.state_hash());
+}
+
+#[test]
+fn test_diverged_checkpoint() {
+    let mut w = world::World::default();
+    w.tick();
+    w.start_recording(MapSource::Default);
+    w.send_player_command(PlayerCommand::Move(Direction::East));
+    w.tick();
+
+    let mut r = w.stop_recording().unwrap();
+    r.checkpoints.last_mut().unwrap().hash ^= 1;
+
+    match r.play() {
+        Err(ReplayError::Diverged(_)) => (),
+        _ => panic!("the divergence is not detected"),
+    }
+}
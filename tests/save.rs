@@ -0,0 +1,99 @@
+extern crate world;
+extern crate specs;
+
+use std::env;
+use specs::Join;
+use world::PlayerCommand;
+use world::components::{Position, PlayerControlled, Energy, Script};
+
+fn player_positions(w: &world::World) -> Vec<(u32, u32, u32)> {
+    let e = w.entities();
+    let (pos_es, player_es) = (e.read::<Position>(), e.read::<PlayerControlled>());
+    (&pos_es, &player_es).iter().map(|(p, _)| (p.x, p.y, p.z)).collect()
+}
+
+#[test]
+fn test_save_and_load() {
+    let path = env::temp_dir().join("rogue_test.save");
+    let path = path.to_str().unwrap();
+
+    let mut w = world::World::default();
+    w.map().lock().unwrap()[(3, 3, 0)] = 205;
+    w.render_view().lock().unwrap().position = (1, 2, 0);
+    w.save(path).unwrap();
+
+    let loaded = world::World::load(path).unwrap();
+    assert_eq!(loaded.map().lock().unwrap()[(3, 3, 0)], 205);
+    assert_eq!(loaded.map().lock().unwrap().size(), w.map().lock().unwrap().size());
+    assert_eq!(loaded.render_view().lock().unwrap().position, (1, 2, 0));
+    assert_eq!(player_positions(&loaded), player_positions(&w));
+    // the prefabs are loaded with the game
+    assert!(loaded.spawn("rat", (12, 10, 0)).is_ok());
+}
+
+#[test]
+fn test_load_scripts() {
+    let path = env::temp_dir().join("rogue_test_scripts.save");
+    let path = path.to_str().unwrap();
+
+    let mut w = world::World::default();
+    w.create_at((12, 10, 0))
+        .with(Energy::new(world::scheduler::NORMAL_SPEED))
+        .with(Script::new("wisp"))
+        .build();
+    w.save(path).unwrap();
+
+    // the wisp drifts only if its script is loaded with the game
+    let mut loaded = world::World::load(path).unwrap();
+    let wisp_position = |w: &world::World| {
+        let e = w.entities();
+        let (pos_es, script_es) = (e.read::<Position>(), e.read::<Script>());
+        let p = (&pos_es, &script_es).iter().next().unwrap().0;
+        (p.x, p.y, p.z)
+    };
+
+    let mut moved = false;
+    for _ in 0..20 {
+        loaded.send_player_command(PlayerCommand::Wait);
+        loaded.tick();
+        loaded.wait();
+        moved |= wisp_position(&loaded) != (12, 10, 0);
+    }
+    assert!(moved);
+}
+
+#[test]
+fn test_same_game_after_load() {
+    let path = env::temp_dir().join("rogue_test_ids.save");
+    let path = path.to_str().unwrap();
+
+    // the deleted rat leaves a gap in the entities' own ids, the loaded game has none
+    let mut w = world::World::default();
+    let rat = w.spawn("rat", (1, 1, 0)).unwrap();
+    w.delete(rat);
+    w.spawn("will-o'-wisp", (12, 10, 0)).unwrap();
+    w.save(path).unwrap();
+
+    let mut loaded = world::World::load(path).unwrap();
+    for _ in 0..20 {
+        w.send_player_command(PlayerCommand::Wait);
+        w.tick();
+        loaded.send_player_command(PlayerCommand::Wait);
+        loaded.tick();
+    }
+    assert_eq!(loaded.state_hash(), w.state_hash());
+}
+
+#[test]
+fn test_load_wrong_version() {
+    use std::fs::File;
+    use std::io::Write;
+
+    let path = env::temp_dir().join("rogue_test_old.save");
+    File::create(&path).unwrap().write_all(b"{\"version\": 0}").unwrap();
+
+    match world::World::load(path.to_str().unwrap()) {
+        Err(world::save::SaveError::Version(0)) => (),
+        _ => panic!("version mismatch is not detected"),
+    }
+}
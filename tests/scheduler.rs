@@ -0,0 +1,42 @@
+extern crate world;
+
+use world::{PlayerCommand, Direction};
+use world::scheduler::{Scheduler, Step, TimeMode};
+
+#[test]
+fn test_turn_based() {
+    let mut s = Scheduler::new(TimeMode::TurnBased);
+
+    // the player is ready, but there's nothing to do
+    assert_eq!(s.next_step(1.0, true), Step::Idle);
+    assert_eq!(s.turn(), 0);
+
+    s.push_command(PlayerCommand::Move(Direction::North));
+    assert_eq!(s.next_step(0.0, true), Step::Act(PlayerCommand::Move(Direction::North)));
+
+    // time passes until the player is ready again
+    assert_eq!(s.next_step(0.0, false), Step::Advance);
+    assert_eq!(s.next_step(0.0, false), Step::Advance);
+    assert_eq!(s.turn(), 2);
+
+    // commands wait for the player
+    s.push_command(PlayerCommand::Move(Direction::South));
+    assert_eq!(s.next_step(0.0, false), Step::Advance);
+    assert_eq!(s.next_step(0.0, true), Step::Act(PlayerCommand::Move(Direction::South)));
+}
+
+#[test]
+fn test_real_time() {
+    let mut s = Scheduler::new(TimeMode::RealTime { turns_per_second: 10.0 });
+
+    assert_eq!(s.next_step(0.05, true), Step::Idle);
+    assert_eq!(s.next_step(0.06, true), Step::Advance);
+    assert_eq!(s.turn(), 1);
+
+    // the player acts without waiting for the next turn
+    s.push_command(PlayerCommand::Wait);
+    assert_eq!(s.next_step(0.0, true), Step::Act(PlayerCommand::Wait));
+
+    // turns pass even if the player does nothing
+    assert_eq!(s.next_step(0.1, true), Step::Advance);
+}
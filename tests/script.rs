@@ -0,0 +1,173 @@
+extern crate world;
+extern crate specs;
+
+mod common;
+
+use specs::Join;
+use world::{PlayerCommand, Direction};
+use world::components::{Position, PlayerControlled, Energy, Health, Inventory, Script, Name};
+use world::script::{ScriptRegistry, ScriptError};
+use common::perform;
+
+fn setup_world() -> world::World {
+    let w = common::setup_world((5, 5, 1), &[((3, 2, 0), "trap")]);
+    w.scripts().lock().unwrap().load_dir("assets/scripts").unwrap();
+
+    common::player(&w)
+        .with(Energy::new(world::scheduler::NORMAL_SPEED))
+        .with(Health::new(20))
+        .with(Name::new("you"))
+        .with(Inventory::new(50.0))
+        .build();
+    w
+}
+
+fn player_health(w: &world::World) -> i32 {
+    let e = w.entities();
+    let (health_es, player_es) = (e.read::<Health>(), e.read::<PlayerControlled>());
+    let h = (&health_es, &player_es).iter().next().unwrap().0.current;
+    h
+}
+
+#[test]
+fn test_trigger() {
+    let mut w = setup_world();
+    w.tick();
+
+    assert_eq!(perform(&mut w, PlayerCommand::Move(Direction::East)), Ok(()));
+
+    // the trap deals 2 to 5 damage and is gone
+    let h = player_health(&w);
+    assert!(h >= 15 && h <= 18, "health is {}", h);
+
+    let floor = w.terrain().cell_of("floor").unwrap();
+    assert_eq!(w.map().lock().unwrap()[(3, 2, 0)], floor);
+    assert!(w.message_log().lock().unwrap().recent(10, 0).iter().any(|m| m.text == "A spike trap hurts you!"));
+}
+
+#[test]
+fn test_behaviour() {
+    let mut w = setup_world();
+    w.scripts().lock().unwrap().add("east", "fn on_turn(id) { move_by(id, 1, 0); }").unwrap();
+
+    let monster = w.create_at((0, 0, 0))
+        .with(Energy::new(world::scheduler::NORMAL_SPEED))
+        .with(Script::new("east"))
+        .build();
+
+    w.tick();
+    assert_eq!(perform(&mut w, PlayerCommand::Wait), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::Wait), Ok(()));
+
+    let e = w.entities();
+    let pos_es = e.read::<Position>();
+    assert_eq!(pos_es.get(monster).map(|p| (p.x, p.y, p.z)), Some((2, 0, 0)));
+}
+
+#[test]
+fn test_item_effect() {
+    let mut w = setup_world();
+    w.spawn("scroll of mending", (2, 2, 0)).unwrap();
+    {
+        let e = w.entities();
+        let mut health_es = e.write::<Health>();
+        for h in (&mut health_es).iter() {
+            h.current = 3;
+        }
+    }
+
+    w.tick();
+    assert_eq!(perform(&mut w, PlayerCommand::PickUp), Ok(()));
+    assert_eq!(perform(&mut w, PlayerCommand::Use(0)), Ok(()));
+
+    assert_eq!(player_health(&w), 20);
+    assert!(w.player_inventory().is_empty());
+}
+
+#[test]
+fn test_parse_error() {
+    let mut scripts = ScriptRegistry::new();
+    match scripts.add("broken", "fn on_turn(id) {") {
+        Err(ScriptError::Parse(_)) => (),
+        _ => panic!("the syntax error is not reported"),
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    let mut scripts = ScriptRegistry::new();
+    let broken = ["fn on_turn(id) { let = 1; }", "fn on_turn(id) { 1 = 2; }", "message(\"outside\");",
+                  "fn f() {} fn f() {}", "fn on_turn(id) { \"open }", "fn on_turn(id) { /* open }",
+                  "fn on_turn(id) { a b }", "fn on_turn(id) { let x = 99999999999999999999; }"];
+
+    for source in &broken {
+        match scripts.add("broken", source) {
+            Err(ScriptError::Parse(_)) => (),
+            r => panic!("the syntax error in {} is not reported: {:?}", source, r),
+        }
+    }
+}
+
+/// Calls `fn test(id)` of the source and returns the messages it left.
+fn run(source: &str) -> Result<Vec<String>, ScriptError> {
+    let mut scripts = ScriptRegistry::new();
+    try!(scripts.add("test", source));
+    try!(scripts.call("test", "test", 0));
+    let messages = scripts.state().lock().unwrap().messages.clone();
+    Ok(messages)
+}
+
+#[test]
+fn test_language() {
+    let source = r#"
+        // the factorial
+        fn fact(n) {
+            if n < 2 {
+                return 1;
+            }
+            return n * fact(n - 1);
+        }
+
+        /* all of the
+           syntax */
+        fn test(id) {
+            let a = [1, 2 + 3 * 4, "x"];
+            let total = 0;
+            total = total + a[1] % 5 - -1;
+
+            if total == 5 && !(a[2] != "x") {
+                message("total " + total);
+            } else if total > 5 || false {
+                message("too much");
+            } else {
+                message("too little");
+            }
+
+            message("fact " + fact(5));
+            message("" + a + " " + ());
+
+            let x = 1;
+            if true {
+                let x = 2;
+                x = 3;
+            }
+            message("x " + x)
+        }
+    "#;
+
+    assert_eq!(run(source).unwrap(), vec!["total 5", "fact 120", "[1, 14, x] ()", "x 1"]);
+}
+
+#[test]
+fn test_run_errors() {
+    let failing = ["fn test(id) { unknown(id); }", "fn test(id) { let x = 1 / 0; }", "fn test(id) { test(id); }",
+                   "fn test(id) { let a = [1]; a[1]; }", "fn test(id) { message(1); }", "fn test(id) { if id {} }",
+                   "fn test(id) { move_by(id, 1); }", "fn test(id) { y = 1; }", "fn other(id) {}"];
+
+    for source in &failing {
+        match run(source) {
+            Err(ScriptError::Run(_)) => (),
+            r => panic!("{} doesn't fail: {:?}", source, r),
+        }
+    }
+}
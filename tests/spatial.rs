@@ -0,0 +1,105 @@
+extern crate world;
+extern crate specs;
+
+mod common;
+
+use world::{PlayerCommand, Direction};
+use world::components::{BlocksMovement, WantsToMove};
+use world::spatial::SpatialIndex;
+
+fn entities(n: usize) -> Vec<specs::Entity> {
+    let w = specs::World::new();
+    (0..n).map(|_| w.create_now().build()).collect()
+}
+
+#[test]
+fn test_tile_queries() {
+    let es = entities(3);
+    let mut index = SpatialIndex::new();
+
+    index.insert(es[0], (1, 1, 0));
+    index.insert(es[1], (1, 1, 0));
+    index.insert(es[2], (2, 1, 0));
+    assert_eq!(index.at((1, 1, 0)), &[es[0], es[1]]);
+    assert_eq!(index.len(), 3);
+
+    // moving keeps a single entry
+    index.insert(es[0], (2, 1, 0));
+    assert_eq!(index.at((1, 1, 0)), &[es[1]]);
+    assert_eq!(index.at((2, 1, 0)), &[es[2], es[0]]);
+    assert_eq!(index.position(es[0]), Some((2, 1, 0)));
+    assert_eq!(index.len(), 3);
+
+    index.remove(es[1]);
+    assert!(index.at((1, 1, 0)).is_empty());
+    assert_eq!(index.position(es[1]), None);
+}
+
+#[test]
+fn test_area_queries() {
+    let es = entities(4);
+    let mut index = SpatialIndex::new();
+
+    index.insert(es[0], (5, 5, 0));
+    index.insert(es[1], (3, 4, 0));
+    index.insert(es[2], (8, 5, 0));
+    index.insert(es[3], (5, 5, 1));
+
+    // small rectangles are searched cell by cell, large ones entity by entity
+    assert_eq!(index.in_rect((3, 4, 0), (2, 2)), vec![es[1]]);
+    assert_eq!(index.in_rect((3, 4, 0), (3, 2)), vec![es[1], es[0]]);
+    assert_eq!(index.in_rect((0, 0, 0), (100, 100)), vec![es[1], es[0], es[2]]);
+    assert_eq!(index.in_rect((0, 0, 1), (100, 100)), vec![es[3]]);
+    assert!(index.in_rect((6, 0, 0), (2, 100)).is_empty());
+
+    // (3, 4) is sqrt(5) away, (8, 5) is 3 away
+    assert_eq!(index.in_radius((5, 5, 0), 2), vec![es[0]]);
+    assert_eq!(index.in_radius((5, 5, 0), 3), vec![es[1], es[0], es[2]]);
+    assert_eq!(index.in_radius((0, 0, 0), 1), vec![]);
+
+    // huge radii are clamped to the coordinates
+    assert_eq!(index.in_radius((5, 5, 0), ::std::u32::MAX), vec![es[1], es[0], es[2]]);
+    assert_eq!(index.in_radius((::std::u32::MAX, 5, 1), ::std::u32::MAX), vec![es[3]]);
+}
+
+#[test]
+fn test_world_index() {
+    let mut w = common::setup_world((5, 5, 1), &[]);
+    let player = common::player(&w).build();
+    let rat = w.spawn("rat", (0, 0, 0)).unwrap();
+
+    {
+        let index = w.spatial_index().lock().unwrap();
+        assert_eq!(index.at((2, 2, 0)), &[player]);
+        assert_eq!(index.at((0, 0, 0)), &[rat]);
+    }
+
+    w.tick();
+    w.send_player_command(PlayerCommand::Move(Direction::East));
+    w.tick();
+
+    let index = w.spatial_index().lock().unwrap();
+    assert!(index.at((2, 2, 0)).is_empty());
+    assert_eq!(index.at((3, 2, 0)), &[player]);
+}
+
+#[test]
+fn test_same_dispatch_moves() {
+    let mut w = common::setup_world((5, 5, 1), &[]);
+
+    // both want the cell between them, the first one moved takes it
+    let first = w.create_at((0, 4, 0))
+        .with(BlocksMovement::default())
+        .with(WantsToMove::new(1, 0, 0))
+        .build();
+    let second = w.create_at((2, 4, 0))
+        .with(BlocksMovement::default())
+        .with(WantsToMove::new(-1, 0, 0))
+        .build();
+
+    w.tick();
+
+    let index = w.spatial_index().lock().unwrap();
+    assert_eq!(index.at((1, 4, 0)), &[first]);
+    assert_eq!(index.at((2, 4, 0)), &[second]);
+}
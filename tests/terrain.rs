@@ -0,0 +1,24 @@
+extern crate world;
+
+use world::terrain::TerrainRegistry;
+
+#[test]
+fn test_terrain_registry() {
+    let t = TerrainRegistry::load("assets/terrain.json").unwrap();
+
+    assert!(t.is_passable(46));
+    assert!(!t.is_passable(205));
+    assert!(t.blocks_sight(188));
+    assert_eq!(t.get(43).name, "door");
+
+    // undefined cells fall back to the default terrain
+    assert_eq!(t.get(250).name, "floor");
+
+    let wall = t.cell_of("wall").unwrap();
+    assert!(!t.is_passable(wall));
+    assert!(t.cell_of("lava").is_none());
+
+    let up = t.stairs_cell(world::terrain::Stairs::Up).unwrap();
+    assert_eq!(t.stairs(up), Some(world::terrain::Stairs::Up));
+    assert_eq!(t.stairs(wall), None);
+}
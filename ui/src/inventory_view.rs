@@ -0,0 +1,101 @@
+use glium::glutin::VirtualKeyCode;
+use tile_map;
+use log_view::put_line;
+use world::PlayerCommand;
+use world::components::Item;
+
+const TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+const EQUIPPED_COLOR: [u8; 4] = [120, 200, 255, 255];
+
+/// What happens to the chosen item.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Use the item, or equip it if it can be equipped.
+    Use,
+    Drop,
+}
+
+/// Index of the item with the letter hotkey, `A` is the first item.
+pub fn key_to_index(code: VirtualKeyCode) -> Option<usize> {
+    use glium::glutin::VirtualKeyCode::*;
+
+    let letters = [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z];
+    letters.iter().position(|&k| k == code)
+}
+
+fn letter(idx: usize) -> char {
+    (b'a' + idx as u8) as char
+}
+
+/// The full-screen list of the items carried by the player.
+pub struct InventoryView {
+    mode: Option<Mode>,
+}
+
+impl InventoryView {
+    pub fn new() -> Self {
+        InventoryView {
+            mode: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.mode.is_some()
+    }
+
+    pub fn open(&mut self, mode: Mode) {
+        self.mode = Some(mode);
+    }
+
+    pub fn close(&mut self) {
+        self.mode = None;
+    }
+
+    /// Returns the command for the item with the index and closes the view.
+    pub fn choose(&mut self, idx: usize, items: &[Item]) -> Option<PlayerCommand> {
+        let item = match items.get(idx) {
+            Some(i) => i,
+            None => return None,
+        };
+
+        let cmd = match self.mode.take() {
+            Some(Mode::Use) if item.is_equippable() => PlayerCommand::Equip(idx),
+            Some(Mode::Use) => PlayerCommand::Use(idx),
+            Some(Mode::Drop) => PlayerCommand::Drop(idx),
+            None => return None,
+        };
+        Some(cmd)
+    }
+
+    /// Draws the list over the whole map. Should be called after the world is drawn.
+    pub fn draw(&self, map: &mut tile_map::TileMap, items: &[Item]) {
+        let title = match self.mode {
+            Some(Mode::Use) => "Use or equip which item? Press its letter, Esc to close",
+            Some(Mode::Drop) => "Drop which item? Press its letter, Esc to close",
+            None => return,
+        };
+
+        let (_, h) = map.size();
+        put_line(map, 0, title, TEXT_COLOR);
+
+        let weight: f32 = items.iter().map(|i| i.total_weight()).sum();
+        put_line(map, 1, &format!("Carried weight: {:.1}", weight), TEXT_COLOR);
+
+        for y in 2..h {
+            let idx = (y - 2) as usize;
+            match items.get(idx) {
+                // only the items with a hotkey are listed
+                Some(i) if idx < 26 => {
+                    let equipped = if i.equipped { " (equipped)" } else { "" };
+                    let line = format!("{}) {}{} {:.1}", letter(idx), i.describe(), equipped, i.total_weight());
+                    put_line(map, y, &line, if i.equipped { EQUIPPED_COLOR } else { TEXT_COLOR });
+                },
+                _ => put_line(map, y, "", TEXT_COLOR),
+            }
+        }
+
+        if items.is_empty() && h > 2 {
+            put_line(map, 2, "You carry nothing.", TEXT_COLOR);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use glium::glutin::{VirtualKeyCode, ElementState};
+use cfg::CfgError;
+use cfg::ui::KeymapCfg;
+use world::Direction;
+
+/// What a key does in the main view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Move(Direction),
+    Wait,
+    Ascend,
+    Descend,
+    PickUp,
+    /// Open the inventory to use or equip an item.
+    Inventory,
+    /// Open the inventory to drop an item.
+    Drop,
+    Open,
+    Close,
+    Interact,
+    /// Generate a random level.
+    NewLevel,
+    /// Toggle the message history.
+    History,
+    ScrollUp,
+    ScrollDown,
+    Quit,
+}
+
+fn parse_action(s: &str) -> Result<Action, CfgError> {
+    let a = match s {
+        "move_n" => Action::Move(Direction::North),
+        "move_ne" => Action::Move(Direction::NorthEast),
+        "move_e" => Action::Move(Direction::East),
+        "move_se" => Action::Move(Direction::SouthEast),
+        "move_s" => Action::Move(Direction::South),
+        "move_sw" => Action::Move(Direction::SouthWest),
+        "move_w" => Action::Move(Direction::West),
+        "move_nw" => Action::Move(Direction::NorthWest),
+        "wait" => Action::Wait,
+        "ascend" => Action::Ascend,
+        "descend" => Action::Descend,
+        "pick_up" => Action::PickUp,
+        "inventory" => Action::Inventory,
+        "drop" => Action::Drop,
+        "open" => Action::Open,
+        "close" => Action::Close,
+        "interact" => Action::Interact,
+        "new_level" => Action::NewLevel,
+        "history" => Action::History,
+        "scroll_up" => Action::ScrollUp,
+        "scroll_down" => Action::ScrollDown,
+        "quit" => Action::Quit,
+        other => return Err(CfgError::Invalid(format!("Unknown action: '{}'", other))),
+    };
+    Ok(a)
+}
+
+macro_rules! key_codes {
+    ($($key:ident),*) => {
+        /// Returns the key with the name from `cfg::ui::KEY_NAMES`.
+        fn key_code(name: &str) -> Option<VirtualKeyCode> {
+            $(
+                if name == stringify!($key) {
+                    return Some(VirtualKeyCode::$key);
+                }
+            )*
+            None
+        }
+    }
+}
+
+with_key_names!(key_codes);
+
+/// State of the modifier keys. Glutin doesn't report them with the key events, so they're
+/// tracked separately.
+#[derive(Clone, Copy, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    /// Updates the state if the key is a modifier.
+    pub fn update(&mut self, state: ElementState, code: VirtualKeyCode) {
+        let pressed = state == ElementState::Pressed;
+        match code {
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift => self.shift = pressed,
+            VirtualKeyCode::LControl | VirtualKeyCode::RControl => self.ctrl = pressed,
+            _ => (),
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<(VirtualKeyCode, bool, bool), Action>,
+}
+
+impl Keymap {
+    pub fn from_cfg(c: &KeymapCfg) -> Result<Keymap, CfgError> {
+        let mut bindings = HashMap::new();
+
+        for (key, action) in try!(c.bindings()) {
+            let code = match key_code(&key.name) {
+                Some(code) => code,
+                None => return Err(CfgError::Invalid(format!("Unsupported key: '{}'", key.name))),
+            };
+            bindings.insert((code, key.shift, key.ctrl), try!(parse_action(&action)));
+        }
+
+        Ok(Keymap {
+            bindings: bindings,
+        })
+    }
+
+    pub fn action(&self, code: VirtualKeyCode, m: Modifiers) -> Option<Action> {
+        self.bindings.get(&(code, m.shift, m.ctrl)).cloned()
+    }
+
+    /// Returns the direction of the key, if it's bound to a move.
+    pub fn direction(&self, code: VirtualKeyCode, m: Modifiers) -> Option<Direction> {
+        match self.action(code, m) {
+            Some(Action::Move(d)) => Some(d),
+            _ => None,
+        }
+    }
+}
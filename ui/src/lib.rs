@@ -7,16 +7,23 @@ extern crate rand;
 #[macro_use]
 extern crate log;
 extern crate world;
+#[macro_use]
 extern crate cfg;
 
 mod tile_map;
 mod world_view;
+mod log_view;
+mod inventory_view;
+mod keymap;
 
 use rand::Rng;
 
 const SCREEN_WIDTH: u32 = 1024;
 const SCREEN_HEIGHT: u32 = 760;
 
+/// Monsters are drawn with their glyph in this colour.
+const MONSTER_COLOR: [u8; 4] = [255, 60, 60, 255];
+
 struct Viewport {
     position: (u32, u32),
     size: (u32, u32),
@@ -27,47 +34,57 @@ trait Renderable {
         where F: glium::backend::Facade, S: glium::Surface;
 }
 
-fn randomize_map(map: &mut world::map::Map) {
-    let nothing = 0;
-    let wall = 1;
+/// Number of z-levels of the generated dungeons.
+const DUNGEON_DEPTH: u32 = 3;
+
+/// Generates a new dungeon, each z-level with a random generator and seed. The levels are
+/// connected with stairs.
+fn random_level(terrain: &world::terrain::TerrainRegistry, (sx, sy, _): (u32, u32, u32))
+                -> Result<world::gen::Level, world::gen::GenError> {
+    use world::gen::{self, Generator};
 
-    let (mx, my, mz) = map.size();
+    let tiles = gen::Tiles::from_terrain(terrain).unwrap();
     let mut rng = rand::thread_rng();
 
-    map.fill(nothing);
+    let mut levels = Vec::new();
+    for _ in 0..DUNGEON_DEPTH {
+        let seed = rng.gen::<u64>();
+        let generator: Box<Generator> = match rng.gen_range(0, 3) {
+            0 => Box::new(gen::bsp::BspGenerator::new(seed, tiles)),
+            1 => Box::new(gen::cave::CaveGenerator::new(seed, tiles)),
+            _ => Box::new(gen::drunkard::DrunkardGenerator::new(seed, tiles)),
+        };
 
-    for z in 0..mz {
-        // top and bottom wall
-        for x in 0..mx {
-            map[(x, 0, z)] = wall;
-            map[(x, my - 1, z)] = wall;
-        }
+        info!("generating a new level, seed: {}", seed);
+        levels.push(try!(generator.generate((sx, sy, 1))));
+    }
 
-        // left and right wall
-        for y in 0..my {
-            map[(0, y, z)] = wall;
-            map[(mx - 1, y, z)] = wall;
-        }
+    let mut level = gen::stack(levels).unwrap();
+    if !gen::connect_levels(&mut level, terrain, rng.gen::<u64>()) {
+        warn!("some levels of the dungeon are not connected");
+    }
+    Ok(level)
+}
 
-        // random boxes
-        let cnt = 100;
-        for _ in 0..cnt {
-            let x = rng.gen_range(1, mx - 1);
-            let y = rng.gen_range(1, my - 1);
-            let w = rng.gen_range(2, 5);
-            let h = rng.gen_range(2, 5);
-
-            for j in y..y+h {
-                for i in x..x+w {
-                    if i >= mx || j >= my {
-                        continue;
-                    }
+/// Returns the names of the prefabs placed in the random levels: the monsters, which act on their
+/// own, and the items.
+fn level_prefabs(prefabs: &cfg::prefabs::PrefabSetCfg) -> (Vec<String>, Vec<String>) {
+    let (mut monsters, mut items) = (Vec::new(), Vec::new());
 
-                    map[(i, j, z)] = wall;
-                }
-            }
+    for p in &prefabs.prefabs {
+        let c = match prefabs.resolve(&p.name) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+
+        if c.item.is_some() {
+            items.push(p.name.clone());
+        } else if c.player != Some(true) && (c.ai.is_some() || c.script.is_some()) {
+            monsters.push(p.name.clone());
         }
     }
+
+    (monsters, items)
 }
 
 pub fn put_str(map: &mut tile_map::TileMap, x: u32, y: u32, s: &[u8]) {
@@ -85,10 +102,15 @@ pub fn put_str(map: &mut tile_map::TileMap, x: u32, y: u32, s: &[u8]) {
     }
 }
 
-pub fn start() {
+/// Runs the game in a window. Fails if the ui config, e.g. its key bindings, is invalid.
+pub fn start() -> Result<(), cfg::CfgError> {
     use glium::{DisplayBuild, Surface};
     use glium::glutin::{Event, VirtualKeyCode, ElementState};
 
+    let cfg = try!(cfg::ui::load("assets/ui.json"));
+    let keymap = try!(keymap::Keymap::from_cfg(&cfg.keymap));
+    let tex_atlas_cfg = try!(cfg::assets::load_atlas(&cfg.map.atlas_path));
+
     let mut world = world::World::default();
 
     let display = glium::glutin::WindowBuilder::new()
@@ -96,8 +118,7 @@ pub fn start() {
         .build_glium()
         .unwrap();
 
-    let cfg = cfg::ui::load("assets/ui.json").unwrap();
-    let tex_atlas_cfg = cfg::assets::load_atlas(&cfg.map.atlas_path).unwrap();
+    let (monsters, items) = level_prefabs(world.prefabs());
     let tex_atlas = tex_atlas::load(&display, &tex_atlas_cfg.path,
                                     tex_atlas_cfg.tile_size, tex_atlas_cfg.tile_count,
                                     tex_atlas_cfg.color_mask).unwrap();
@@ -109,7 +130,10 @@ pub fn start() {
         }
     };
 
-    let dwarf_cfg = cfg.map.tiles.get("dwarf").unwrap();
+    let dwarf_cfg = match cfg.map.tiles.get("dwarf") {
+        Some(c) => c,
+        None => return Err(cfg::CfgError::Invalid("The ui config has no 'dwarf' tile".to_owned())),
+    };
 
     let map_size = (SCREEN_WIDTH / visible_tile_size.0, SCREEN_HEIGHT / visible_tile_size.1);
     let mut viewport = Viewport { position: (0, 0), size: (SCREEN_WIDTH, SCREEN_HEIGHT) };
@@ -118,36 +142,112 @@ pub fn start() {
     let mut t0 = time::precise_time_s();
     let mut frames = 0;
 
+    let mut log_view = log_view::LogView::new();
+    let mut inventory_view = inventory_view::InventoryView::new();
+
+    // a command waiting for its direction
+    let mut pending: Option<fn(world::Direction) -> world::PlayerCommand> = None;
+    let mut modifiers = keymap::Modifiers::default();
+    // the number of dungeons generated so far, names the levels
+    let mut levels_made = 0;
+
     loop {
         {
             for ev in display.poll_events() {
+                if let Event::KeyboardInput(state, _, Some(code)) = ev {
+                    modifiers.update(state, code);
+                }
+
                 match ev {
-                    Event::Closed | Event::KeyboardInput(_, _, Some(VirtualKeyCode::Escape)) => return,
-                    Event::KeyboardInput(ElementState::Pressed, _, Some(code)) => {
+                    Event::KeyboardInput(ElementState::Pressed, _, Some(code)) if inventory_view.is_open() => {
+                        if code == VirtualKeyCode::Escape {
+                            inventory_view.close();
+                        } else if let Some(idx) = inventory_view::key_to_index(code) {
+                            if let Some(cmd) = inventory_view.choose(idx, &world.player_inventory()) {
+                                world.send_player_command(cmd);
+                            }
+                        }
+                    },
+                    Event::KeyboardInput(ElementState::Pressed, _, Some(code)) if log_view.is_history() => {
+                        let page = log_view.page_size(&tile_map);
+                        let log = world.message_log().lock().unwrap();
                         match code {
-                            VirtualKeyCode::W => {
-                                world.send_player_command(world::PlayerCommand::MoveUp);
-                            },
-                            VirtualKeyCode::S => {
-                                world.send_player_command(world::PlayerCommand::MoveDown);
-                            },
-                            VirtualKeyCode::A => {
-                                world.send_player_command(world::PlayerCommand::MoveLeft);
-                            },
-                            VirtualKeyCode::D => {
-                                world.send_player_command(world::PlayerCommand::MoveRight);
+                            VirtualKeyCode::Escape => log_view.toggle_history(),
+                            VirtualKeyCode::Up => log_view.scroll_up(&log, 1),
+                            VirtualKeyCode::Down => log_view.scroll_down(1),
+                            VirtualKeyCode::PageUp => log_view.scroll_up(&log, page),
+                            VirtualKeyCode::PageDown => log_view.scroll_down(page),
+                            _ if keymap.action(code, modifiers) == Some(keymap::Action::History) => {
+                                log_view.toggle_history();
                             },
-                            VirtualKeyCode::R => {
-                                randomize_map(&mut *world.map_mut().lock().unwrap());
-                            },
-                            VirtualKeyCode::B => {
+                            _ => (),
+                        }
+                    },
+                    Event::KeyboardInput(ElementState::Pressed, _, Some(code)) if pending.is_some() => {
+                        let f = pending.take().unwrap();
+                        if let Some(d) = keymap.direction(code, modifiers) {
+                            world.send_player_command(f(d));
+                        }
+                    },
+                    Event::Closed => return Ok(()),
+                    Event::KeyboardInput(ElementState::Pressed, _, Some(code)) => {
+                        use keymap::Action;
+                        use world::PlayerCommand;
+
+                        let action = match keymap.action(code, modifiers) {
+                            Some(a) => a,
+                            None => continue,
+                        };
+
+                        match action {
+                            Action::Move(d) => world.send_player_command(PlayerCommand::Move(d)),
+                            Action::Wait => world.send_player_command(PlayerCommand::Wait),
+                            Action::Ascend => world.send_player_command(PlayerCommand::Ascend),
+                            Action::Descend => world.send_player_command(PlayerCommand::Descend),
+                            Action::PickUp => world.send_player_command(PlayerCommand::PickUp),
+                            Action::Inventory => inventory_view.open(inventory_view::Mode::Use),
+                            Action::Drop => inventory_view.open(inventory_view::Mode::Drop),
+                            Action::Open => pending = Some(PlayerCommand::Open),
+                            Action::Close => pending = Some(PlayerCommand::Close),
+                            Action::Interact => pending = Some(PlayerCommand::Interact),
+                            Action::NewLevel => {
+                                let size = world.map().lock().unwrap().size();
+                                let level = match random_level(world.terrain(), size) {
+                                    Ok(l) => l,
+                                    Err(e) => {
+                                        warn!("can't generate a new level: {:?}", e);
+                                        continue;
+                                    },
+                                };
+                                let rooms = level.rooms.clone();
+                                // every new dungeon replaces the old one for good
+                                levels_made += 1;
+                                world.set_level(&format!("dungeon {}", levels_made), level);
+
+                                // the deeper the darker
+                                {
+                                    let mut lighting = world.lighting().lock().unwrap();
+                                    for z in 0..DUNGEON_DEPTH {
+                                        let ambient = 0.6 / (z + 1) as f32;
+                                        lighting.set_ambient(z, [ambient, ambient, ambient]);
+                                    }
+                                }
+
+                                // a monster in every room but the player's, an item in every room
                                 let mut rng = rand::thread_rng();
-                                let (mw, mh) = tile_map.size();
-                                let x = rng.gen_range(0, mw);
-                                let y = rng.gen_range(0, mh);
-                                put_str(&mut tile_map, x, y, b"Hello!");
+                                for room in rooms.iter().skip(1) {
+                                    let m = rng.choose(&monsters).unwrap();
+                                    world.spawn(m, room.center()).unwrap();
+                                }
+                                for room in &rooms {
+                                    let i = rng.choose(&items).unwrap();
+                                    world.spawn(i, room.center()).unwrap();
+                                }
                             },
-                            _ => (),
+                            Action::History => log_view.toggle_history(),
+                            Action::ScrollUp => log_view.scroll_up(&world.message_log().lock().unwrap(), 1),
+                            Action::ScrollDown => log_view.scroll_down(1),
+                            Action::Quit => return Ok(()),
                         }
                     },
                     Event::Resized(w, h) => {
@@ -162,26 +262,40 @@ pub fn start() {
 
         world.tick();
 
+        // the results are shown in the message log
+        world.command_reports();
+        world.combat_events();
+
         {
             let view = &mut world.render_view().lock().unwrap();
-            view.size = (map_size.0, map_size.1, 1);
+            let (w, h) = tile_map.size();
+            view.size = (w, h.saturating_sub(log_view::PANEL_HEIGHT), 1);
         }
 
         {
+            let terrain = world.terrain();
             if let Some(ref rendered_view) = *world.last_render().lock().unwrap() {
                 let converter = move |t: &world::tile::Tile| {
+                    let terrain = terrain.get(t.ground);
+
                     if let Some(ref fx) = t.effects {
                         for e in fx {
-                            if let world::tile::Effect::Marked(_) = *e {
-                                return world_view::TileVariant::Entity(*dwarf_cfg);
+                            if let world::tile::Effect::Marked(mark) = *e {
+                                if mark == world::components::PLAYER_MARK {
+                                    return world_view::TileVariant::Entity(*dwarf_cfg);
+                                }
+                                return world_view::TileVariant::Ground(mark, MONSTER_COLOR, terrain.bg);
                             }
                         }
                     }
 
-                    world_view::TileVariant::Simple(t.ground)
+                    world_view::TileVariant::Ground(t.ground, terrain.fg, terrain.bg)
                 };
                 world_view::update(&mut tile_map, rendered_view, converter);
             }
+
+            log_view.draw(&mut tile_map, &world.message_log().lock().unwrap());
+            inventory_view.draw(&mut tile_map, &world.player_inventory());
         }
 
         let mut target = display.draw();
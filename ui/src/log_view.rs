@@ -0,0 +1,103 @@
+use std::cmp::min;
+use tile_map;
+use world_view::to_vec4;
+use world::messages::{Message, MessageLog};
+
+/// Height of the log panel under the map, in tiles.
+pub const PANEL_HEIGHT: u32 = 5;
+
+const TITLE: &'static str = "Message history: Up/Down, PageUp/PageDown to scroll, Esc to close";
+
+/// Draws a line of text, the rest of the row is cleared. Characters out of ASCII are shown as `?`.
+pub fn put_line(map: &mut tile_map::TileMap, y: u32, s: &str, color: [u8; 4]) {
+    let (w, _) = map.size();
+    let mut chars = s.chars();
+
+    for x in 0..w {
+        let n = match chars.next() {
+            Some(c) if (c as u32) < 128 => c as u8,
+            Some(_) => b'?',
+            None => b' ',
+        };
+
+        map.set_tile(x, y, tile_map::Tile {
+            n: n,
+            fg_color: to_vec4(color),
+            .. Default::default()
+        });
+    }
+}
+
+fn line_of(m: &Message) -> String {
+    format!("{:>5} {}", m.turn, m.full_text())
+}
+
+/// Shows the message log either in a panel under the map or in the full-screen history view.
+pub struct LogView {
+    /// Number of the newest messages scrolled out of the view.
+    scroll: usize,
+    history: bool,
+}
+
+impl LogView {
+    pub fn new() -> Self {
+        LogView {
+            scroll: 0,
+            history: false,
+        }
+    }
+
+    pub fn is_history(&self) -> bool {
+        self.history
+    }
+
+    pub fn toggle_history(&mut self) {
+        self.history = !self.history;
+        self.scroll = 0;
+    }
+
+    /// Scrolls towards the older messages.
+    pub fn scroll_up(&mut self, log: &MessageLog, n: usize) {
+        self.scroll = min(self.scroll + n, log.len().saturating_sub(1));
+    }
+
+    /// Scrolls towards the newer messages.
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll = self.scroll.saturating_sub(n);
+    }
+
+    /// Number of messages shown at once, used to scroll by pages.
+    pub fn page_size(&self, map: &tile_map::TileMap) -> usize {
+        if self.history {
+            map.size().1.saturating_sub(1) as usize
+        } else {
+            PANEL_HEIGHT as usize
+        }
+    }
+
+    /// Draws the panel at the bottom of the map or the history over the whole map. Should be
+    /// called after the world is drawn.
+    pub fn draw(&self, map: &mut tile_map::TileMap, log: &MessageLog) {
+        let (_, h) = map.size();
+
+        let (top, rows) = if self.history {
+            put_line(map, 0, TITLE, [255, 255, 255, 255]);
+            (1, h.saturating_sub(1))
+        } else {
+            let rows = min(PANEL_HEIGHT, h);
+            (h - rows, rows)
+        };
+
+        let messages = log.recent(rows as usize, self.scroll);
+
+        // the newest message is at the bottom
+        let empty = rows as usize - messages.len();
+        for i in 0..rows {
+            let y = top + i;
+            match (i as usize).checked_sub(empty).and_then(|j| messages.get(j)) {
+                Some(m) => put_line(map, y, &line_of(m), m.color),
+                None => put_line(map, y, "", [255, 255, 255, 255]),
+            }
+        }
+    }
+}
@@ -2,13 +2,27 @@ use tile_map;
 use cfg;
 use world;
 
-fn to_vec4(v: [u8; 4]) -> [f32; 4] {
+pub fn to_vec4(v: [u8; 4]) -> [f32; 4] {
     [v[0] as f32 / 255.0, v[1] as f32 / 255.0, v[2] as f32 / 255.0, v[3] as f32 / 255.0]
 }
 
+/// Remembered tiles out of the player's sight are drawn darker.
+const OUT_OF_SIGHT_BRIGHTNESS: f32 = 0.3;
+
+fn dim(c: [f32; 4]) -> [f32; 4] {
+    [c[0] * OUT_OF_SIGHT_BRIGHTNESS, c[1] * OUT_OF_SIGHT_BRIGHTNESS, c[2] * OUT_OF_SIGHT_BRIGHTNESS, c[3]]
+}
+
+/// Multiplies the colour by the light falling on the tile.
+fn tint(c: [f32; 4], light: [f32; 3]) -> [f32; 4] {
+    let f = |i: usize| (c[i] * light[i]).min(1.0);
+    [f(0), f(1), f(2), c[3]]
+}
+
 pub enum TileVariant {
     Entity(cfg::ui::UiMapTileCfg),
-    Simple(u8),
+    /// (tile, fg, bg)
+    Ground(u8, [u8; 4], [u8; 4]),
 }
 
 pub fn update<F>(tile_map: &mut tile_map::TileMap,
@@ -17,21 +31,33 @@ pub fn update<F>(tile_map: &mut tile_map::TileMap,
     where F: Fn(&world::tile::Tile) -> TileVariant {
 
     for (x, y, _, t) in rendered_view.iter() {
-        match converter(t) {
-            TileVariant::Simple(t) => {
-                tile_map.set_tile(x, y, tile_map::Tile {
-                    n: t,
-                    fg_color: to_vec4([255, 255, 255, 255]),
-                    .. Default::default()
-                })
-            },
+        if !t.explored {
+            // never seen tiles are hidden
+            tile_map.set_tile(x, y, tile_map::Tile {
+                visible: false,
+                .. Default::default()
+            });
+            continue;
+        }
+
+        let (n, fg, bg) = match converter(t) {
+            TileVariant::Ground(n, fg, bg) => (n, to_vec4(fg), to_vec4(bg)),
             TileVariant::Entity(cfg) => {
-                tile_map.set_tile(x, y, tile_map::Tile {
-                    n: cfg.tile_n as u8, // FIXME get rid of casting
-                    fg_color: to_vec4(cfg.fg),
-                    .. Default::default()
-                });
+                (cfg.tile_n as u8, to_vec4(cfg.fg), to_vec4([0, 0, 0, 255])) // FIXME get rid of casting
             },
-        }
+        };
+
+        let (fg, bg) = match (t.visible, t.light()) {
+            (true, Some(l)) => (tint(fg, l), tint(bg, l)),
+            (true, None) => (fg, bg),
+            (false, _) => (dim(fg), dim(bg)),
+        };
+
+        tile_map.set_tile(x, y, tile_map::Tile {
+            n: n,
+            fg_color: fg,
+            bg_color: [bg[0], bg[1], bg[2]],
+            .. Default::default()
+        });
     }
 }
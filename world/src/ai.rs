@@ -0,0 +1,136 @@
+use cfg;
+use path::{self, PathCost, Point, Neighbourhood};
+use rng::Rng;
+
+/// A single behaviour of a monster. See `decide`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Behaviour {
+    /// Stand still.
+    Idle,
+    /// Move in a random direction.
+    Wander,
+    /// Move towards the player when the player is seen.
+    Chase,
+    /// Run away from the player when the health is below the fraction of the maximum.
+    Flee(f32),
+    /// Stay at the distance from the player: retreat if the player is closer, approach if
+    /// the player is further.
+    KeepDistance(u32),
+}
+
+/// Builds the behaviours of a monster type, the most urgent ones first.
+pub fn behaviours_from_cfg(c: &cfg::monsters::AiCfg) -> Result<Vec<Behaviour>, cfg::CfgError> {
+    let mut behaviours = Vec::new();
+
+    if let Some(f) = c.flee_below {
+        behaviours.push(Behaviour::Flee(f));
+    }
+
+    if let Some(d) = c.keep_distance {
+        behaviours.push(Behaviour::KeepDistance(d));
+    }
+
+    if c.chase {
+        behaviours.push(Behaviour::Chase);
+    }
+
+    match c.idle.as_ref() {
+        "idle" => behaviours.push(Behaviour::Idle),
+        "wander" => behaviours.push(Behaviour::Wander),
+        other => return Err(cfg::CfgError::Invalid(format!("Unknown idle behaviour: '{}'", other))),
+    }
+
+    Ok(behaviours)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Wait,
+    /// Move to the adjacent cell.
+    Move(Point),
+}
+
+/// What a monster knows when it decides what to do.
+pub struct Situation {
+    pub position: Point,
+    pub map_size: (u32, u32, u32),
+    /// Position of the player, if the monster sees the player.
+    pub player: Option<Point>,
+    /// Health as a fraction of the maximum, if the monster has health.
+    pub health: Option<f32>,
+}
+
+const FLEE_COEF: f32 = -1.2;
+
+/// Number of steps between the points on the same z-level, ignoring obstacles.
+pub fn distance(a: Point, b: Point) -> u32 {
+    let d = |a: u32, b: u32| if a > b { a - b } else { b - a };
+    ::std::cmp::max(d(a.0, b.0), d(a.1, b.1))
+}
+
+fn approach<C: PathCost>(cost: &C, s: &Situation, target: Point) -> Option<Action> {
+    path::find_path(cost, s.map_size, s.position, target, Neighbourhood::Eight)
+        .and_then(|p| p.first().cloned())
+        .map(Action::Move)
+}
+
+fn retreat<C: PathCost>(cost: &C, s: &Situation, from: Point) -> Option<Action> {
+    path::DijkstraMap::new(cost, s.map_size, &[from], Neighbourhood::Eight)
+        .flee(cost, FLEE_COEF)
+        .next_step(cost, s.position)
+        .map(Action::Move)
+}
+
+fn wander<C: PathCost>(cost: &C, s: &Situation, rng: &mut Rng) -> Option<Action> {
+    let (x, y, z) = s.position;
+    let (sx, sy, _) = s.map_size;
+
+    let mut options = Vec::new();
+    for &(dx, dy) in &[(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)] {
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        if nx < 0 || ny < 0 || nx >= sx as i32 || ny >= sy as i32 {
+            continue;
+        }
+
+        let next = (nx as u32, ny as u32, z);
+        if cost.cost(s.position, next).is_some() {
+            options.push(next);
+        }
+    }
+
+    Some(rng.choose(&options).map_or(Action::Wait, |&p| Action::Move(p)))
+}
+
+impl Behaviour {
+    /// Returns the action or `None` if the behaviour doesn't apply in the situation.
+    fn decide<C: PathCost>(&self, cost: &C, s: &Situation, rng: &mut Rng) -> Option<Action> {
+        match *self {
+            Behaviour::Idle => Some(Action::Wait),
+            Behaviour::Wander => wander(cost, s, rng),
+            Behaviour::Chase => s.player.and_then(|p| approach(cost, s, p)),
+            Behaviour::Flee(below) => match (s.player, s.health) {
+                (Some(p), Some(h)) if h < below => retreat(cost, s, p),
+                _ => None,
+            },
+            Behaviour::KeepDistance(d) => s.player.and_then(|p| {
+                let current = distance(s.position, p);
+                if current < d {
+                    retreat(cost, s, p)
+                } else if current > d {
+                    approach(cost, s, p)
+                } else {
+                    Some(Action::Wait)
+                }
+            }),
+        }
+    }
+}
+
+/// Tries the behaviours in order, the first one which applies decides the action. Monsters
+/// without any applicable behaviour wait.
+pub fn decide<C: PathCost>(behaviours: &[Behaviour], cost: &C, s: &Situation, rng: &mut Rng) -> Action {
+    behaviours.iter()
+        .filter_map(|b| b.decide(cost, s, rng))
+        .next()
+        .unwrap_or(Action::Wait)
+}
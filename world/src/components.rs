@@ -1,7 +1,9 @@
+use std::collections::HashSet;
 use specs;
 
 /// `Position`
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Position {
     pub x: u32,
     pub y: u32,
@@ -24,6 +26,7 @@ impl specs::Component for Position {
 
 /// `PlayerControlled`
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PlayerControlled;
 
 impl Default for PlayerControlled {
@@ -38,6 +41,13 @@ impl specs::Component for PlayerControlled {
 
 /// `Visible`
 
+/// Mark of the player's entities. Other entities are marked with their CP437 glyph.
+pub const PLAYER_MARK: u8 = 1;
+
+/// `%`
+pub const CORPSE_MARK: u8 = 37;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Visible {
     pub mark: u8,
 }
@@ -45,7 +55,7 @@ pub struct Visible {
 impl Default for Visible {
     fn default() -> Self {
         Visible {
-            mark: 1,
+            mark: PLAYER_MARK,
         }
     }
 }
@@ -53,3 +63,462 @@ impl Default for Visible {
 impl specs::Component for Visible {
     type Storage = specs::VecStorage<Visible>;
 }
+
+/// `Viewer`
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Viewer {
+    pub radius: u32,
+    /// Cells currently in the field of view. Updated by the FOV system.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub visible: HashSet<(u32, u32, u32)>,
+}
+
+impl Viewer {
+    pub fn new(radius: u32) -> Self {
+        Viewer {
+            radius: radius,
+            visible: HashSet::new(),
+        }
+    }
+
+    pub fn can_see(&self, x: u32, y: u32, z: u32) -> bool {
+        self.visible.contains(&(x, y, z))
+    }
+}
+
+impl specs::Component for Viewer {
+    type Storage = specs::VecStorage<Viewer>;
+}
+
+/// `Energy`
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Energy {
+    /// Energy gained every turn.
+    pub speed: u32,
+    /// The entity can act when it has at least `scheduler::ACTION_COST`.
+    pub value: i32,
+}
+
+impl Energy {
+    pub fn new(speed: u32) -> Self {
+        Energy {
+            speed: speed,
+            value: 0,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.value >= ::scheduler::ACTION_COST
+    }
+
+    pub fn spend(&mut self) {
+        self.value -= ::scheduler::ACTION_COST;
+    }
+}
+
+impl specs::Component for Energy {
+    type Storage = specs::VecStorage<Energy>;
+}
+
+/// `BlocksMovement`
+
+/// Other entities can't enter the cell occupied by this one.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BlocksMovement;
+
+impl Default for BlocksMovement {
+    fn default() -> Self {
+        BlocksMovement
+    }
+}
+
+impl specs::Component for BlocksMovement {
+    type Storage = specs::VecStorage<BlocksMovement>;
+}
+
+/// `WantsToMove`
+
+/// An intent to move by the offset on the entity's next action. Removed by the movement system
+/// whether the move succeeds or not.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WantsToMove {
+    pub dx: i32,
+    pub dy: i32,
+    pub dz: i32,
+}
+
+impl WantsToMove {
+    pub fn new(dx: i32, dy: i32, dz: i32) -> Self {
+        WantsToMove {
+            dx: dx,
+            dy: dy,
+            dz: dz,
+        }
+    }
+}
+
+impl specs::Component for WantsToMove {
+    type Storage = specs::VecStorage<WantsToMove>;
+}
+
+/// `WantsToAttack`
+
+/// An intent to attack the target on the entity's next action.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WantsToAttack {
+    pub target: specs::Entity,
+}
+
+impl WantsToAttack {
+    pub fn new(target: specs::Entity) -> Self {
+        WantsToAttack {
+            target: target,
+        }
+    }
+}
+
+impl specs::Component for WantsToAttack {
+    type Storage = specs::VecStorage<WantsToAttack>;
+}
+
+/// `Health`
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Health {
+    pub fn new(max: i32) -> Self {
+        Health {
+            current: max,
+            max: max,
+        }
+    }
+
+    /// Returns the current health as a fraction of the maximum.
+    pub fn fraction(&self) -> f32 {
+        if self.max <= 0 {
+            return 0.0;
+        }
+
+        self.current as f32 / self.max as f32
+    }
+}
+
+impl specs::Component for Health {
+    type Storage = specs::VecStorage<Health>;
+}
+
+/// `Ai`
+
+/// Makes the entity act on its own. See `ai::decide`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ai {
+    pub behaviours: Vec<::ai::Behaviour>,
+}
+
+impl Ai {
+    pub fn new(behaviours: Vec<::ai::Behaviour>) -> Self {
+        Ai {
+            behaviours: behaviours,
+        }
+    }
+}
+
+impl specs::Component for Ai {
+    type Storage = specs::VecStorage<Ai>;
+}
+
+/// `CombatStats`
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CombatStats {
+    /// A hit deals from 1 to `power` damage.
+    pub power: i32,
+    /// Every point lowers the attackers' chance to hit by 5%.
+    pub defense: i32,
+    /// Chance to hit a target without defense.
+    pub accuracy: f32,
+}
+
+impl CombatStats {
+    pub fn new(power: i32, defense: i32, accuracy: f32) -> Self {
+        CombatStats {
+            power: power,
+            defense: defense,
+            accuracy: accuracy,
+        }
+    }
+}
+
+impl specs::Component for CombatStats {
+    type Storage = specs::VecStorage<CombatStats>;
+}
+
+/// `Dead`
+
+/// Dead monsters are replaced with corpses after the turn, dead players stay.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Dead;
+
+impl Default for Dead {
+    fn default() -> Self {
+        Dead
+    }
+}
+
+impl specs::Component for Dead {
+    type Storage = specs::VecStorage<Dead>;
+}
+
+/// `Name`
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Name {
+    pub name: String,
+}
+
+impl Name {
+    pub fn new(name: &str) -> Self {
+        Name {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl specs::Component for Name {
+    type Storage = specs::VecStorage<Name>;
+}
+
+/// `StableId`
+
+/// An id which, unlike the entity's own, stays the same when the game is saved and loaded. It
+/// seeds the random decisions of the entity, see `rng::entity_seed`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StableId {
+    pub id: u32,
+}
+
+impl StableId {
+    pub fn new(id: u32) -> Self {
+        StableId {
+            id: id,
+        }
+    }
+
+    /// The stable id of the entity, or its own id if it has none.
+    pub fn of(e: specs::Entity, id: Option<&StableId>) -> u32 {
+        id.map_or(e.get_id(), |i| i.id)
+    }
+}
+
+impl specs::Component for StableId {
+    type Storage = specs::VecStorage<StableId>;
+}
+
+/// `Item`
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ItemKind {
+    Misc,
+    /// Restores the health when used.
+    Potion { heal: i32 },
+    /// Adds to the power of the wielder.
+    Weapon { power: i32 },
+    /// Adds to the defense of the wearer.
+    Armor { defense: i32 },
+}
+
+impl ItemKind {
+    pub fn power_bonus(&self) -> i32 {
+        match *self {
+            ItemKind::Weapon { power } => power,
+            _ => 0,
+        }
+    }
+
+    pub fn defense_bonus(&self) -> i32 {
+        match *self {
+            ItemKind::Armor { defense } => defense,
+            _ => 0,
+        }
+    }
+}
+
+/// Something that can be carried. Items lying on the floor have a `Position`, items in an
+/// `Inventory` don't.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    /// CP437 code of the item's glyph.
+    pub glyph: u8,
+    /// Weight of a single item of the stack.
+    pub weight: f32,
+    /// Stackable items of the same name are carried as a single stack.
+    pub stackable: bool,
+    pub count: u32,
+    pub kind: ItemKind,
+    pub equipped: bool,
+    /// Name of the script called when the item is used.
+    #[serde(default)]
+    pub script: Option<String>,
+}
+
+impl Item {
+    pub fn new(name: &str, glyph: u8, weight: f32, stackable: bool, kind: ItemKind) -> Self {
+        Item {
+            name: name.to_owned(),
+            glyph: glyph,
+            weight: weight,
+            stackable: stackable,
+            count: 1,
+            kind: kind,
+            equipped: false,
+            script: None,
+        }
+    }
+
+    pub fn from_cfg(c: &::cfg::items::ItemCfg) -> Result<Self, ::cfg::CfgError> {
+        let kind = match c.kind.as_ref() {
+            "misc" => ItemKind::Misc,
+            "potion" => ItemKind::Potion { heal: c.heal },
+            "weapon" => ItemKind::Weapon { power: c.power },
+            "armor" => ItemKind::Armor { defense: c.defense },
+            other => return Err(::cfg::CfgError::Invalid(format!("Unknown item kind: '{}'", other))),
+        };
+
+        let mut item = Item::new(&c.name, c.glyph, c.weight, c.stackable, kind);
+        item.script = c.script.clone();
+        Ok(item)
+    }
+
+    pub fn total_weight(&self) -> f32 {
+        self.weight * self.count as f32
+    }
+
+    pub fn is_equippable(&self) -> bool {
+        match self.kind {
+            ItemKind::Weapon { .. } | ItemKind::Armor { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Can the other item be added to this item's stack.
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        self.stackable && other.stackable && self.name == other.name && self.kind == other.kind &&
+            self.script == other.script
+    }
+
+    /// Returns the name with the count of a stack, e.g. "arrow x3".
+    pub fn describe(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.name, self.count)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+impl specs::Component for Item {
+    type Storage = specs::VecStorage<Item>;
+}
+
+/// `Inventory`
+
+/// Items carried by an actor. The items are entities with the `Item` component and without
+/// a `Position`.
+#[derive(Clone)]
+pub struct Inventory {
+    pub items: Vec<specs::Entity>,
+    /// The total weight of the items can't exceed this.
+    pub max_weight: f32,
+}
+
+impl Inventory {
+    pub fn new(max_weight: f32) -> Self {
+        Inventory {
+            items: Vec::new(),
+            max_weight: max_weight,
+        }
+    }
+}
+
+impl specs::Component for Inventory {
+    type Storage = specs::VecStorage<Inventory>;
+}
+
+/// `LightSource`
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LightSource {
+    /// Cells further than this are not lit.
+    pub radius: u32,
+    pub color: ::light::Color,
+    /// Light at the source, it fades out towards the radius.
+    pub intensity: f32,
+    /// Fraction of the intensity which randomly changes every frame, 0.0 is a steady light.
+    pub flicker: f32,
+}
+
+impl LightSource {
+    pub fn new(radius: u32, color: ::light::Color, intensity: f32, flicker: f32) -> Self {
+        LightSource {
+            radius: radius,
+            color: color,
+            intensity: intensity,
+            flicker: flicker,
+        }
+    }
+}
+
+impl specs::Component for LightSource {
+    type Storage = specs::VecStorage<LightSource>;
+}
+
+/// `Script`
+
+/// Behaviour defined by a script: its `on_turn` is called with the entity's id whenever the
+/// entity can act.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Script {
+    pub name: String,
+}
+
+impl Script {
+    pub fn new(name: &str) -> Self {
+        Script {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl specs::Component for Script {
+    type Storage = specs::VecStorage<Script>;
+}
+
+/// `WantsToRunScript`
+
+/// An intent to call a function of a script with the entity's id, e.g. the `on_use` of an
+/// item. Removed by the scripting system.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WantsToRunScript {
+    pub script: String,
+    pub function: String,
+}
+
+impl WantsToRunScript {
+    pub fn new(script: &str, function: &str) -> Self {
+        WantsToRunScript {
+            script: script.to_owned(),
+            function: function.to_owned(),
+        }
+    }
+}
+
+impl specs::Component for WantsToRunScript {
+    type Storage = specs::VecStorage<WantsToRunScript>;
+}
@@ -0,0 +1,108 @@
+/// Multipliers for transforming coordinates into each of the eight octants.
+const OCTANTS: [[i32; 8]; 4] = [
+    [1, 0, 0, -1, -1, 0, 0, 1],
+    [0, 1, -1, 0, 0, -1, 1, 0],
+    [0, 1, 1, 0, 0, -1, -1, 0],
+    [1, 0, 0, 1, -1, 0, 0, -1],
+];
+
+/// Calculates the field of view from `origin` within the specified `radius`.
+/// `blocks(x, y)` must return `true` for the cells which block sight. `visit(x, y)` is called for
+/// every visible cell, including the origin and the visible obstacles. Cells outside of `size` are
+/// never visited.
+pub fn compute<B, V>(origin: (u32, u32), radius: u32, size: (u32, u32), blocks: B, mut visit: V)
+    where B: Fn(u32, u32) -> bool, V: FnMut(u32, u32) {
+
+    let (ox, oy) = origin;
+    if ox >= size.0 || oy >= size.1 {
+        return;
+    }
+
+    visit(ox, oy);
+
+    let mut caster = Caster {
+        origin: (ox as i32, oy as i32),
+        radius: radius as i32,
+        size: (size.0 as i32, size.1 as i32),
+        blocks: &blocks,
+        visit: &mut visit,
+    };
+
+    for oct in 0..8 {
+        let t = (OCTANTS[0][oct], OCTANTS[1][oct], OCTANTS[2][oct], OCTANTS[3][oct]);
+        caster.cast(1, 1.0, 0.0, t);
+    }
+}
+
+struct Caster<'a, B: 'a, V: 'a> {
+    origin: (i32, i32),
+    radius: i32,
+    size: (i32, i32),
+    blocks: &'a B,
+    visit: &'a mut V,
+}
+
+impl<'a, B, V> Caster<'a, B, V> where B: Fn(u32, u32) -> bool, V: FnMut(u32, u32) {
+    fn is_inside(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.size.0 && y < self.size.1
+    }
+
+    fn is_blocked(&self, x: i32, y: i32) -> bool {
+        !self.is_inside(x, y) || (self.blocks)(x as u32, y as u32)
+    }
+
+    /// Scans rows starting from `row`, lighting cells between the `start` and `end` slopes.
+    /// `(xx, xy, yx, yy)` transforms the coordinates into the current octant.
+    fn cast(&mut self, row: i32, mut start: f32, end: f32, (xx, xy, yx, yy): (i32, i32, i32, i32)) {
+        if start < end {
+            return;
+        }
+
+        let (cx, cy) = self.origin;
+        let radius_sq = self.radius * self.radius;
+        let mut new_start = 0.0;
+
+        for j in row..self.radius + 1 {
+            let dy = -j;
+            let mut dx = -j - 1;
+            let mut blocked = false;
+
+            while dx <= 0 {
+                dx += 1;
+
+                let x = cx + dx * xx + dy * xy;
+                let y = cy + dx * yx + dy * yy;
+
+                let l_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let r_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+
+                if start < r_slope {
+                    continue;
+                } else if end > l_slope {
+                    break;
+                }
+
+                if dx * dx + dy * dy <= radius_sq && self.is_inside(x, y) {
+                    (self.visit)(x as u32, y as u32);
+                }
+
+                if blocked {
+                    if self.is_blocked(x, y) {
+                        new_start = r_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if self.is_blocked(x, y) && j < self.radius {
+                    blocked = true;
+                    self.cast(j + 1, start, l_slope, (xx, xy, yx, yy));
+                    new_start = r_slope;
+                }
+            }
+
+            if blocked {
+                break;
+            }
+        }
+    }
+}
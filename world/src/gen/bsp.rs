@@ -0,0 +1,134 @@
+use std::cmp::{min, max};
+use map;
+use rng::Rng;
+use super::{Generator, GenError, Level, Rect, Tiles};
+
+/// Rooms and corridors. Each z-level is recursively split into partitions, every partition gets
+/// a room and the sibling partitions are connected with corridors.
+pub struct BspGenerator {
+    seed: u64,
+    tiles: Tiles,
+    /// Partitions smaller than this are not split further.
+    pub min_partition_size: u32,
+    pub min_room_size: u32,
+}
+
+impl BspGenerator {
+    pub fn new(seed: u64, tiles: Tiles) -> Self {
+        BspGenerator {
+            seed: seed,
+            tiles: tiles,
+            min_partition_size: 8,
+            min_room_size: 3,
+        }
+    }
+
+    fn split(&self, rng: &mut Rng, map: &mut map::Map, area: Rect) -> Vec<Rect> {
+        let min_size = self.min_partition_size;
+        let can_split_x = area.w >= min_size * 2;
+        let can_split_y = area.h >= min_size * 2;
+
+        if !can_split_x && !can_split_y {
+            return vec![self.place_room(rng, map, area)];
+        }
+
+        // prefer cutting across the longer side
+        let split_x = if can_split_x && can_split_y {
+            if area.w * 4 > area.h * 5 {
+                true
+            } else if area.h * 4 > area.w * 5 {
+                false
+            } else {
+                rng.chance(0.5)
+            }
+        } else {
+            can_split_x
+        };
+
+        let (a, b) = if split_x {
+            let at = rng.gen_range(min_size, area.w - min_size + 1);
+            (Rect::new(area.x, area.y, area.z, at, area.h),
+             Rect::new(area.x + at, area.y, area.z, area.w - at, area.h))
+        } else {
+            let at = rng.gen_range(min_size, area.h - min_size + 1);
+            (Rect::new(area.x, area.y, area.z, area.w, at),
+             Rect::new(area.x, area.y + at, area.z, area.w, area.h - at))
+        };
+
+        let mut rooms = self.split(rng, map, a);
+        let other = self.split(rng, map, b);
+
+        let from = rng.choose(&rooms).unwrap().center();
+        let to = rng.choose(&other).unwrap().center();
+        self.dig_corridor(rng, map, from, to);
+
+        rooms.extend(other);
+        rooms
+    }
+
+    fn place_room(&self, rng: &mut Rng, map: &mut map::Map, area: Rect) -> Rect {
+        let (x, w) = random_span(rng, area.x, area.w, self.min_room_size);
+        let (y, h) = random_span(rng, area.y, area.h, self.min_room_size);
+        let room = Rect::new(x, y, area.z, w, h);
+
+        for j in y..y + h {
+            for i in x..x + w {
+                map[(i, j, area.z)] = self.tiles.floor;
+            }
+        }
+
+        room
+    }
+
+    /// Digs an L-shaped corridor.
+    fn dig_corridor(&self, rng: &mut Rng, map: &mut map::Map, from: (u32, u32, u32), to: (u32, u32, u32)) {
+        let (x1, y1, z) = from;
+        let (x2, y2, _) = to;
+
+        let corner = if rng.chance(0.5) { (x2, y1) } else { (x1, y2) };
+
+        for x in min(x1, x2)..max(x1, x2) + 1 {
+            map[(x, corner.1, z)] = self.tiles.floor;
+        }
+
+        for y in min(y1, y2)..max(y1, y2) + 1 {
+            map[(corner.0, y, z)] = self.tiles.floor;
+        }
+    }
+}
+
+/// Picks a random segment of `len` cells starting at `start`, keeping one cell margins if
+/// possible. Returns the start and the length of the segment.
+fn random_span(rng: &mut Rng, start: u32, len: u32, min_len: u32) -> (u32, u32) {
+    let (start, len) = if len > 2 { (start + 1, len - 2) } else { (start, len) };
+    let min_len = max(min(min_len, len), 1);
+
+    let l = rng.gen_range(min_len, len + 1);
+    let s = start + rng.gen_range(0, len - l + 1);
+    (s, l)
+}
+
+impl Generator for BspGenerator {
+    fn generate(&self, size: (u32, u32, u32)) -> Result<Level, GenError> {
+        try!(super::check_size(size));
+
+        let (sx, sy, sz) = size;
+        let mut map = map::Map::new(size, self.tiles.wall);
+        let mut rooms = Vec::new();
+
+        for z in 0..sz {
+            let mut rng = Rng::new(super::level_seed(self.seed, z));
+            let area = Rect::new(1, 1, z, sx - 2, sy - 2);
+            rooms.extend(self.split(&mut rng, &mut map, area));
+        }
+
+        // every z-level has at least one room
+        let spawn = rooms[0].center();
+
+        Ok(Level {
+            map: map,
+            rooms: rooms,
+            spawn: spawn,
+        })
+    }
+}
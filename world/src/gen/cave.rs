@@ -0,0 +1,172 @@
+use map;
+use rng::Rng;
+use super::{Generator, GenError, Level, Tiles};
+
+/// Caves made with a cellular automaton. Only the largest connected cave of each z-level is kept.
+pub struct CaveGenerator {
+    seed: u64,
+    tiles: Tiles,
+    /// Chance of a cell to start as a wall.
+    pub wall_chance: f32,
+    /// Number of smoothing steps.
+    pub iterations: u32,
+}
+
+impl CaveGenerator {
+    pub fn new(seed: u64, tiles: Tiles) -> Self {
+        CaveGenerator {
+            seed: seed,
+            tiles: tiles,
+            wall_chance: 0.45,
+            iterations: 5,
+        }
+    }
+}
+
+/// A single z-level, `true` is a wall.
+struct Cells {
+    size: (u32, u32),
+    data: Vec<bool>,
+}
+
+impl Cells {
+    fn idx(&self, x: u32, y: u32) -> usize {
+        (x + y * self.size.0) as usize
+    }
+
+    fn is_wall(&self, x: i32, y: i32) -> bool {
+        let (sx, sy) = self.size;
+        if x < 0 || y < 0 || x >= sx as i32 || y >= sy as i32 {
+            return true;
+        }
+
+        self.data[self.idx(x as u32, y as u32)]
+    }
+
+    fn walls_around(&self, x: u32, y: u32) -> u32 {
+        let mut n = 0;
+        for dy in -1..2 {
+            for dx in -1..2 {
+                if (dx != 0 || dy != 0) && self.is_wall(x as i32 + dx, y as i32 + dy) {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    fn smooth(&self) -> Cells {
+        let (sx, sy) = self.size;
+        let mut data = Vec::with_capacity(self.data.len());
+
+        for y in 0..sy {
+            for x in 0..sx {
+                let border = x == 0 || y == 0 || x == sx - 1 || y == sy - 1;
+
+                // walls survive with 4 walls around, open cells are filled with 5
+                let n = self.walls_around(x, y);
+                let wall = if self.data[self.idx(x, y)] { n >= 4 } else { n >= 5 };
+
+                data.push(border || wall);
+            }
+        }
+
+        Cells {
+            size: self.size,
+            data: data,
+        }
+    }
+
+    /// Returns the cells of the largest 4-connected open region.
+    fn largest_region(&self) -> Vec<(u32, u32)> {
+        let (sx, sy) = self.size;
+        let mut seen = vec![false; self.data.len()];
+        let mut best = Vec::new();
+
+        for y in 0..sy {
+            for x in 0..sx {
+                let idx = self.idx(x, y);
+                if self.data[idx] || seen[idx] {
+                    continue;
+                }
+
+                let mut region = Vec::new();
+                let mut stack = vec![(x, y)];
+                seen[idx] = true;
+
+                while let Some((cx, cy)) = stack.pop() {
+                    region.push((cx, cy));
+
+                    for &(dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                        let (nx, ny) = (cx as i32 + dx, cy as i32 + dy);
+                        if self.is_wall(nx, ny) {
+                            continue;
+                        }
+
+                        let n_idx = self.idx(nx as u32, ny as u32);
+                        if !seen[n_idx] {
+                            seen[n_idx] = true;
+                            stack.push((nx as u32, ny as u32));
+                        }
+                    }
+                }
+
+                if region.len() > best.len() {
+                    best = region;
+                }
+            }
+        }
+
+        best
+    }
+}
+
+impl Generator for CaveGenerator {
+    fn generate(&self, size: (u32, u32, u32)) -> Result<Level, GenError> {
+        try!(super::check_size(size));
+
+        let (sx, sy, sz) = size;
+        let mut map = map::Map::new(size, self.tiles.wall);
+        let mut spawn = None;
+
+        for z in 0..sz {
+            let mut rng = Rng::new(super::level_seed(self.seed, z));
+
+            let mut cells = Cells {
+                size: (sx, sy),
+                data: (0..sx * sy).map(|_| rng.chance(self.wall_chance)).collect(),
+            };
+
+            for _ in 0..self.iterations {
+                cells = cells.smooth();
+            }
+
+            let mut region = cells.largest_region();
+            if region.is_empty() {
+                // everything is filled, make at least some room
+                region.push((sx / 2, sy / 2));
+            }
+
+            for &(x, y) in &region {
+                map[(x, y, z)] = self.tiles.floor;
+            }
+
+            if z == 0 {
+                // the open cell closest to the center
+                let d = |&&(x, y): &&(u32, u32)| {
+                    let dx = x as i64 - (sx / 2) as i64;
+                    let dy = y as i64 - (sy / 2) as i64;
+                    dx * dx + dy * dy
+                };
+                let &(x, y) = region.iter().min_by_key(d).unwrap();
+                spawn = Some((x, y, 0));
+            }
+        }
+
+        Ok(Level {
+            map: map,
+            rooms: Vec::new(),
+            spawn: spawn.unwrap_or((sx / 2, sy / 2, 0)),
+        })
+    }
+}
@@ -0,0 +1,70 @@
+use map;
+use rng::Rng;
+use super::{Generator, GenError, Level, Tiles};
+
+/// Winding caves dug by a random walk starting at the center of each z-level.
+pub struct DrunkardGenerator {
+    seed: u64,
+    tiles: Tiles,
+    /// Part of the z-level to dig out.
+    pub coverage: f32,
+}
+
+impl DrunkardGenerator {
+    pub fn new(seed: u64, tiles: Tiles) -> Self {
+        DrunkardGenerator {
+            seed: seed,
+            tiles: tiles,
+            coverage: 0.4,
+        }
+    }
+}
+
+impl Generator for DrunkardGenerator {
+    fn generate(&self, size: (u32, u32, u32)) -> Result<Level, GenError> {
+        try!(super::check_size(size));
+
+        let (sx, sy, sz) = size;
+        let mut map = map::Map::new(size, self.tiles.wall);
+
+        let interior = (sx - 2) * (sy - 2);
+        let target = ((interior as f32 * self.coverage) as u32).max(1);
+        // give up eventually, so the walk always ends
+        let max_steps = interior * 100;
+
+        let start = (sx / 2, sy / 2);
+
+        for z in 0..sz {
+            let mut rng = Rng::new(super::level_seed(self.seed, z));
+            let (mut x, mut y) = start;
+
+            map[(x, y, z)] = self.tiles.floor;
+            let mut dug = 1;
+
+            for _ in 0..max_steps {
+                if dug >= target {
+                    break;
+                }
+
+                match rng.gen_range(0, 4) {
+                    0 if y > 1 => y -= 1,
+                    1 if x < sx - 2 => x += 1,
+                    2 if y < sy - 2 => y += 1,
+                    3 if x > 1 => x -= 1,
+                    _ => continue,
+                }
+
+                if map[(x, y, z)] != self.tiles.floor {
+                    map[(x, y, z)] = self.tiles.floor;
+                    dug += 1;
+                }
+            }
+        }
+
+        Ok(Level {
+            map: map,
+            rooms: Vec::new(),
+            spawn: (start.0, start.1, 0),
+        })
+    }
+}
@@ -0,0 +1,171 @@
+use map;
+use terrain;
+use rng::Rng;
+
+pub mod bsp;
+pub mod cave;
+pub mod drunkard;
+
+/// A rectangular area on a z-level.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, z: u32, w: u32, h: u32) -> Self {
+        Rect {
+            x: x,
+            y: y,
+            z: z,
+            w: w,
+            h: h,
+        }
+    }
+
+    pub fn center(&self) -> (u32, u32, u32) {
+        (self.x + self.w / 2, self.y + self.h / 2, self.z)
+    }
+
+    pub fn contains(&self, x: u32, y: u32, z: u32) -> bool {
+        z == self.z && x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+/// A generated map and what is known about its layout.
+pub struct Level {
+    pub map: map::Map,
+    /// Rooms, if the generator produces them.
+    pub rooms: Vec<Rect>,
+    /// A passable cell suitable for placing the player.
+    pub spawn: (u32, u32, u32),
+}
+
+/// Cells used by the generators.
+#[derive(Clone, Copy, Debug)]
+pub struct Tiles {
+    pub floor: map::Cell,
+    pub wall: map::Cell,
+}
+
+impl Tiles {
+    /// Takes the cells of the "floor" and "wall" terrain types.
+    pub fn from_terrain(terrain: &terrain::TerrainRegistry) -> Option<Self> {
+        match (terrain.cell_of("floor"), terrain.cell_of("wall")) {
+            (Some(floor), Some(wall)) => Some(Tiles { floor: floor, wall: wall }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GenError {
+    /// The generator can't make a level of the size.
+    InvalidSize(String),
+}
+
+/// The smallest width and height of a generated level, the open cells are surrounded with walls.
+pub const MIN_SIZE: u32 = 3;
+
+/// Checks that the size has room for a level: at least one z-level of `MIN_SIZE` cells in each
+/// direction.
+fn check_size((sx, sy, sz): (u32, u32, u32)) -> Result<(), GenError> {
+    if sx < MIN_SIZE || sy < MIN_SIZE || sz == 0 {
+        let msg = format!("{:?}, the level must be at least {}x{}x1", (sx, sy, sz), MIN_SIZE, MIN_SIZE);
+        return Err(GenError::InvalidSize(msg));
+    }
+    Ok(())
+}
+
+/// Generators are created with a seed and produce the same level for the same seed and size.
+pub trait Generator {
+    fn generate(&self, size: (u32, u32, u32)) -> Result<Level, GenError>;
+}
+
+/// Derives a seed for a z-level, so each level of a map looks different.
+fn level_seed(seed: u64, z: u32) -> u64 {
+    seed.wrapping_add((z as u64).wrapping_mul(0x9E3779B97F4A7C15))
+}
+
+/// Stacks single-level maps into a multi-level one, the first map becomes the top level. The
+/// levels can come from different generators or TMX files, but must have the same width and
+/// height. Only the top z-level of each map is used, the spawn point is the first level's.
+pub fn stack(levels: Vec<Level>) -> Option<Level> {
+    let (sx, sy, _) = match levels.first() {
+        Some(l) => l.map.size(),
+        None => return None,
+    };
+
+    if levels.iter().any(|l| (l.map.size().0, l.map.size().1) != (sx, sy)) {
+        return None;
+    }
+
+    let mut map = map::Map::new((sx, sy, levels.len() as u32), 0);
+    let mut rooms = Vec::new();
+
+    for (z, l) in levels.iter().enumerate() {
+        let z = z as u32;
+        for y in 0..sy {
+            for x in 0..sx {
+                map[(x, y, z)] = l.map[(x, y, 0)];
+            }
+        }
+
+        rooms.extend(l.rooms.iter().filter(|r| r.z == 0).map(|r| Rect::new(r.x, r.y, z, r.w, r.h)));
+    }
+
+    Some(Level {
+        map: map,
+        rooms: rooms,
+        spawn: levels[0].spawn,
+    })
+}
+
+/// Connects every pair of adjacent z-levels with stairs: stairs down on the upper level and
+/// stairs up right under them. The stairs are placed on a random cell which is passable on both
+/// levels. Returns `false` if the terrain has no stairs or some levels couldn't be connected.
+pub fn connect_levels(level: &mut Level, terrain: &terrain::TerrainRegistry, seed: u64) -> bool {
+    use terrain::Stairs;
+
+    let (up, down) = match (terrain.stairs_cell(Stairs::Up), terrain.stairs_cell(Stairs::Down)) {
+        (Some(up), Some(down)) => (up, down),
+        _ => return false,
+    };
+
+    let (sx, sy, sz) = level.map.size();
+    let mut connected = true;
+
+    for z in 1..sz {
+        let candidates = {
+            let free = |x: u32, y: u32, z: u32| {
+                let c = level.map[(x, y, z)];
+                terrain.is_passable(c) && terrain.stairs(c).is_none() && (x, y, z) != level.spawn
+            };
+
+            let mut candidates = Vec::new();
+            for y in 0..sy {
+                for x in 0..sx {
+                    if free(x, y, z - 1) && free(x, y, z) {
+                        candidates.push((x, y));
+                    }
+                }
+            }
+            candidates
+        };
+
+        let mut rng = Rng::new(level_seed(seed, z));
+        match rng.choose(&candidates).cloned() {
+            Some((x, y)) => {
+                level.map[(x, y, z - 1)] = down;
+                level.map[(x, y, z)] = up;
+            },
+            None => connected = false,
+        }
+    }
+
+    connected
+}
@@ -1,98 +1,677 @@
+#![feature(plugin)]
+#![plugin(serde_macros)]
+#![feature(custom_derive)]
+
 #[macro_use]
 extern crate log;
 extern crate specs;
+extern crate serde;
+extern crate serde_json;
 extern crate time;
+extern crate xml;
+extern crate base64;
+extern crate flate2;
+extern crate cfg;
 
 pub mod map;
 pub mod tile;
+pub mod terrain;
+pub mod fov;
+pub mod path;
+pub mod rng;
+pub mod gen;
 pub mod components;
 pub mod systems;
+pub mod save;
+pub mod scheduler;
+pub mod cp437;
+pub mod ai;
+pub mod messages;
+pub mod light;
+pub mod replay;
+pub mod script;
+pub mod spatial;
 
+use std::collections::HashMap;
+use std::cell::Cell;
+use std::mem;
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-pub use systems::player_control::PlayerCommand;
+pub use systems::player_control::{PlayerCommand, Direction, CommandError, CommandReport};
+pub use systems::combat::{CombatEvent, CombatOutcome};
 
 pub type TimeDelta = f64;
 pub type MapHolder = Arc<Mutex<map::Map>>;
+pub type MapMemoryHolder = Arc<Mutex<map::MapMemory>>;
 
 #[derive(Clone)]
 pub struct WorldContext {
     time_delta: TimeDelta,
+    /// Current turn number.
+    turn: u64,
+    /// Is a turn passing now. Systems which act on game time should do nothing otherwise.
+    advance: bool,
+    /// Seed of all random decisions made by the systems.
+    seed: u64,
     map: MapHolder,
+    memory: MapMemoryHolder,
+    terrain: terrain::TerrainHolder,
+    /// Messages for the player.
+    log: messages::MessageLogHolder,
+    /// Entities by their positions.
+    spatial: spatial::SpatialIndexHolder,
 }
 
 impl WorldContext {
-    pub fn new(time_delta: f64, map: MapHolder) -> Self {
+    pub fn new(time_delta: f64, turn: u64, advance: bool, seed: u64,
+               map: MapHolder, memory: MapMemoryHolder, terrain: terrain::TerrainHolder,
+               log: messages::MessageLogHolder, spatial: spatial::SpatialIndexHolder) -> Self {
         WorldContext {
             time_delta: time_delta,
+            turn: turn,
+            advance: advance,
+            seed: seed,
             map: map,
+            memory: memory,
+            terrain: terrain,
+            log: log,
+            spatial: spatial,
         }
     }
 }
 
+/// Builds an entity with a `Position` like `specs::EntityBuilder`, the built entity is added to
+/// the spatial index. See `World::create_at`.
+pub struct PlacedEntityBuilder<'a> {
+    builder: specs::EntityBuilder<'a>,
+    position: spatial::Point,
+    index: &'a spatial::SpatialIndexHolder,
+}
+
+impl<'a> PlacedEntityBuilder<'a> {
+    pub fn with<T: specs::Component>(self, c: T) -> Self {
+        PlacedEntityBuilder {
+            builder: self.builder.with(c),
+            position: self.position,
+            index: self.index,
+        }
+    }
+
+    pub fn build(self) -> specs::Entity {
+        let e = self.builder.build();
+        self.index.lock().unwrap().insert(e, self.position);
+        e
+    }
+}
+
 pub struct World {
     planner: specs::Planner<WorldContext>,
     map: MapHolder,
+    /// The player's memory of the current level.
+    memory: MapMemoryHolder,
+    /// Name of the current level, see `set_level`.
+    level: String,
+    /// Memories of the levels the player has left, by their names.
+    level_memories: HashMap<String, map::MapMemory>,
+    terrain: terrain::TerrainHolder,
+    log: messages::MessageLogHolder,
+    lighting: light::LightingHolder,
+    spatial: spatial::SpatialIndexHolder,
+    scripts: script::ScriptsHolder,
+    prefabs: cfg::prefabs::PrefabSetCfg,
     player_commands: mpsc::Sender<PlayerCommand>,
+    command_reports: mpsc::Receiver<CommandReport>,
+    combat_events: mpsc::Receiver<CombatEvent>,
     last_render: systems::render::RenderedViewHolder,
     render_view: systems::render::ViewHolder,
+    camera: systems::camera::CameraHolder,
+    scheduler: scheduler::Scheduler,
+    seed: u64,
+    /// The `StableId` of the next created entity.
+    next_id: Cell<u32>,
     last_tick: f64,
+    /// The game being recorded.
+    recording: Option<replay::Replay>,
 }
 
+const TERRAIN_CFG: &'static str = "assets/terrain.json";
+const SCRIPTS_DIR: &'static str = "assets/scripts";
+const PREFABS_CFG: &'static str = "assets/prefabs.json";
+
+/// Registers all components. Saved games contain the components registered here, except for
+/// intents.
+fn register_components(w: &mut specs::World) {
+    w.register::<components::Position>();
+    w.register::<components::Visible>();
+    w.register::<components::PlayerControlled>();
+    w.register::<components::Viewer>();
+    w.register::<components::Energy>();
+    w.register::<components::BlocksMovement>();
+    w.register::<components::Health>();
+    w.register::<components::Ai>();
+    w.register::<components::CombatStats>();
+    w.register::<components::Dead>();
+    w.register::<components::Name>();
+    w.register::<components::Item>();
+    w.register::<components::Inventory>();
+    w.register::<components::LightSource>();
+    w.register::<components::Script>();
+    w.register::<components::StableId>();
+
+    // intents last only until they're performed, so they aren't saved
+    w.register::<components::WantsToMove>();
+    w.register::<components::WantsToAttack>();
+    w.register::<components::WantsToRunScript>();
+}
+
+/// Limits the number of turns processed in a single `World::tick`, in case the player never
+/// gets enough energy to act.
+const MAX_STEPS_PER_TICK: u32 = 1000;
+
 impl Default for World {
     fn default() -> Self {
-        let map = Arc::new(Mutex::new(map::load_from_csv("assets/test_map.csv", (50, 50, 1))));
-        let checker = systems::player_control::MapObstactChecker::new(map.clone());
+        let map = map::load_from_tmx("assets/test.tmx").unwrap();
+        let terrain = Arc::new(terrain::TerrainRegistry::load(TERRAIN_CFG).unwrap());
+        let mut world = World::new(map, terrain);
+        world.load_assets().unwrap();
+
+        // Add a controllable entity
+        world.spawn("player", (10, 10, 0)).unwrap();
+
+        world
+    }
+}
+
+impl World {
+    /// Creates a world without any entities.
+    pub fn new(map: map::Map, terrain: terrain::TerrainHolder) -> Self {
+        let memory = Arc::new(Mutex::new(map::MapMemory::new(map.size())));
+        let map = Arc::new(Mutex::new(map));
+        let checker = systems::player_control::MapObstactChecker::new(map.clone(), terrain.clone());
 
         let (cmd_sender, cmd_receiver) = mpsc::channel();
+        let (report_sender, report_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
 
         let last_render_holder = Arc::new(Mutex::new(None));
         let render_view_holder = Arc::new(Mutex::new(systems::render::View::default()));
+        let camera_holder = Arc::new(Mutex::new(systems::camera::Camera::default()));
+        let lighting_holder = Arc::new(Mutex::new(light::Lighting::default()));
+        let scripts_holder = Arc::new(Mutex::new(script::ScriptRegistry::new()));
 
         let planner = {
             let mut w = specs::World::new();
-            w.register::<components::Position>();
-            w.register::<components::Visible>();
-            w.register::<components::PlayerControlled>();
-
-            // Add a controllable entity
-            w.create_now()
-                .with(components::Position::new(10, 10, 0))
-                .with(components::PlayerControlled::default())
-                .with(components::Visible::default())
-                .build();
+            register_components(&mut w);
 
             let mut p = specs::Planner::new(w, 4);
-            p.add_system(systems::player_control::PlayerControlSystem::new(cmd_receiver, checker), "player-control", 100);
+            p.add_system(systems::energy::EnergySystem::new(), "energy", 50);
+            p.add_system(systems::player_control::PlayerControlSystem::new(cmd_receiver, report_sender.clone()),
+                          "player-control", 100);
+            p.add_system(systems::ai::AiSystem::new(), "ai", 95);
+            p.add_system(systems::script::ScriptSystem::new(scripts_holder.clone()), "script", 93);
+            p.add_system(systems::movement::MovementSystem::new(checker, report_sender), "movement", 90);
+            p.add_system(systems::combat::CombatSystem::new(event_sender), "combat", 85);
+            // the view is updated last, so every frame shows the results of the turn. Systems
+            // start in the order of priority once the previous one has fetched its components,
+            // the camera and the lighting lock their holders before that, so the renderer waits.
+            // Systems which hold several of the holders lock them in one order: the lighting,
+            // the map, then the memory
+            p.add_system(systems::fov::FovSystem::new(), "fov", 40);
+            p.add_system(systems::lighting::LightingSystem::new(lighting_holder.clone()), "lighting", 30);
+            p.add_system(systems::camera::CameraSystem::new(camera_holder.clone(),
+                                                            render_view_holder.clone()), "camera", 20);
             p.add_system(systems::render::RenderingSystem::new(last_render_holder.clone(),
-                                                               render_view_holder.clone()), "rendering", 200);
+                                                               render_view_holder.clone(),
+                                                               lighting_holder.clone()), "rendering", 10);
 
             p
         };
 
         World {
             map: map,
+            memory: memory,
+            level: String::new(),
+            level_memories: HashMap::new(),
+            terrain: terrain,
+            log: Arc::new(Mutex::new(messages::MessageLog::default())),
+            lighting: lighting_holder,
+            spatial: Arc::new(Mutex::new(spatial::SpatialIndex::new())),
+            scripts: scripts_holder,
+            prefabs: cfg::prefabs::PrefabSetCfg::default(),
             player_commands: cmd_sender,
+            command_reports: report_receiver,
+            combat_events: event_receiver,
             last_render: last_render_holder,
             render_view: render_view_holder,
+            camera: camera_holder,
             planner: planner,
+            scheduler: scheduler::Scheduler::new(scheduler::TimeMode::TurnBased),
+            seed: 0,
+            next_id: Cell::new(0),
             last_tick: time::precise_time_s(),
+            recording: None,
         }
     }
-}
 
-impl World {
+    /// Saves the map, the map memory, the view and all entities.
+    pub fn save(&mut self, path: &str) -> Result<(), save::SaveError> {
+        // let the running systems finish
+        self.planner.wait();
+        save::save(self, path)
+    }
+
+    /// Loads a game saved with `World::save`.
+    pub fn load(path: &str) -> Result<World, save::SaveError> {
+        let terrain = match terrain::TerrainRegistry::load(TERRAIN_CFG) {
+            Ok(t) => Arc::new(t),
+            Err(e) => return Err(save::SaveError::Terrain(format!("{:?}", e))),
+        };
+
+        let mut world = try!(save::load(path, terrain));
+        try!(world.load_assets());
+        Ok(world)
+    }
+
+    /// Loads the scripts and the prefabs, which the entities, the terrain and `spawn` refer to by
+    /// name.
+    fn load_assets(&mut self) -> Result<(), save::SaveError> {
+        if let Err(e) = self.scripts.lock().unwrap().load_dir(SCRIPTS_DIR) {
+            return Err(save::SaveError::Assets(format!("{:?}", e)));
+        }
+        if let Err(e) = self.load_prefabs(PREFABS_CFG) {
+            return Err(save::SaveError::Assets(format!("{:?}", e)));
+        }
+        Ok(())
+    }
+
+    /// Processes the player's commands and passes turns according to the time mode.
     pub fn tick(&mut self) {
-        let dt = time::precise_time_s() - self.last_tick;
-        let ctx = WorldContext::new(dt, self.map.clone());
+        use scheduler::Step;
+
+        let now = time::precise_time_s();
+        let mut dt = now - self.last_tick;
+        self.last_tick = now;
+
+        for _ in 0..MAX_STEPS_PER_TICK {
+            let step = {
+                self.planner.wait();
+                self.remove_dead();
+                let ready = self.is_player_ready();
+                self.scheduler.next_step(dt, ready)
+            };
+
+            match step {
+                Step::Act(cmd) => {
+                    self.record(cmd);
+                    if let Err(e) = self.player_commands.send(cmd) {
+                        panic!("Unhandled error while sending player commands: {:?}", e);
+                    }
+                    self.dispatch(dt, false);
+                },
+                Step::Advance => self.dispatch(dt, true),
+                Step::Idle => {
+                    self.dispatch(dt, false);
+                    return;
+                },
+            }
+
+            // the time is accounted only once
+            dt = 0.0;
+        }
+
+        warn!("too many turns have passed without the player's action");
+    }
+
+    /// Passes a turn without the player's action, as in the real-time mode. Used by replays.
+    pub fn pass_turn(&mut self) {
+        self.planner.wait();
+        self.remove_dead();
+        let turn = self.scheduler.turn() + 1;
+        self.scheduler.set_turn(turn);
+        self.dispatch(0.0, true);
+    }
+
+    /// Starts recording the performed commands. The world must be in the state created from the
+    /// source, with the current seed.
+    pub fn start_recording(&mut self, source: replay::MapSource) {
+        self.recording = Some(replay::Replay::new(self.seed, source));
+    }
+
+    /// Stops the recording and returns the recorded game.
+    pub fn stop_recording(&mut self) -> Option<replay::Replay> {
+        let hash = self.state_hash();
+        self.recording.take().map(|mut r| {
+            r.finish(hash);
+            r
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    fn record(&mut self, cmd: PlayerCommand) {
+        let hash = match self.recording {
+            Some(ref r) if r.needs_checkpoint() => Some(save::state_hash(self)),
+            _ => None,
+        };
+
+        let turn = self.scheduler.turn();
+        if let Some(ref mut r) = self.recording {
+            r.record(turn, cmd, hash);
+        }
+    }
+
+    /// Returns a hash of the game's state, the same for the same seed, map source and commands.
+    pub fn state_hash(&mut self) -> u64 {
+        self.planner.wait();
+        self.remove_dead();
+        save::state_hash(self)
+    }
+
+    /// Blocks until the systems started by the last `tick` are finished.
+    pub fn wait(&mut self) {
+        self.planner.wait();
+    }
+
+    fn dispatch(&mut self, dt: TimeDelta, advance: bool) {
+        let ctx = WorldContext::new(dt, self.scheduler.turn(), advance, self.seed,
+                                    self.map.clone(), self.memory.clone(), self.terrain.clone(),
+                                    self.log.clone(), self.spatial.clone());
         self.planner.dispatch(ctx);
     }
 
+    /// Starts building an entity at the position. Entities with a `Position` are created this
+    /// way or by `spawn`, and moved by the systems, so the spatial index always knows them.
+    pub fn create_at(&self, (x, y, z): (u32, u32, u32)) -> PlacedEntityBuilder {
+        use components::Position;
+
+        PlacedEntityBuilder {
+            builder: self.planner.world.create_now().with(Position::new(x, y, z)).with(self.new_id()),
+            position: (x, y, z),
+            index: &self.spatial,
+        }
+    }
+
+    fn new_id(&self) -> components::StableId {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        components::StableId::new(id)
+    }
+
+    /// Deletes the entity and removes it from the spatial index.
+    pub fn delete(&mut self, e: specs::Entity) {
+        self.planner.wait();
+        self.planner.world.delete_now(e);
+        self.spatial.lock().unwrap().remove(e);
+    }
+
+    /// Replaces dead monsters with corpses, the items they carried fall where they died. Dead
+    /// players stay, so the game can show them. Dead entities without a position, like used up
+    /// items, are just removed.
+    fn remove_dead(&mut self) {
+        use components::{Position, PlayerControlled, Dead, Name, Visible, Inventory, Item};
+
+        let w = &self.planner.world;
+
+        // (entity, position, name, carried items)
+        let dead: Vec<(specs::Entity, Option<Position>, Option<String>, Vec<specs::Entity>)> = {
+            let (dead_es, player_es) = (w.read::<Dead>(), w.read::<PlayerControlled>());
+            let (pos_es, name_es, inventory_es) = (w.read::<Position>(), w.read::<Name>(), w.read::<Inventory>());
+
+            w.entities()
+                .filter(|e| dead_es.get(*e).is_some() && player_es.get(*e).is_none())
+                .map(|e| {
+                    let carried = inventory_es.get(e).map_or(Vec::new(), |inv| inv.items.clone());
+                    (e, pos_es.get(e).cloned(), name_es.get(e).map(|n| n.name.clone()), carried)
+                })
+                .collect()
+        };
+
+        let index = &mut self.spatial.lock().unwrap();
+
+        for (e, pos, name, carried) in dead {
+            w.delete_now(e);
+            index.remove(e);
+
+            if let Some(pos) = pos {
+                let name = match name {
+                    Some(n) => format!("{} corpse", n),
+                    None => "corpse".to_owned(),
+                };
+
+                let p = (pos.x, pos.y, pos.z);
+
+                {
+                    let (mut pos_es, mut item_es) = (w.write::<Position>(), w.write::<Item>());
+                    for item in carried {
+                        pos_es.insert(item, pos.clone());
+                        index.insert(item, p);
+                        if let Some(i) = item_es.get_mut(item) {
+                            i.equipped = false;
+                        }
+                    }
+                }
+
+                let corpse = w.create_now()
+                    .with(pos)
+                    .with(self.new_id())
+                    .with(Visible { mark: components::CORPSE_MARK })
+                    .with(Name::new(&name))
+                    .build();
+                index.insert(corpse, p);
+            } else {
+                // nobody would find the items
+                for item in carried {
+                    w.delete_now(item);
+                }
+            }
+        }
+    }
+
+    /// The player is ready if all of the player's entities have enough energy. Entities without
+    /// `Energy` can always act.
+    fn is_player_ready(&self) -> bool {
+        use specs::Join;
+        use components::{PlayerControlled, Energy};
+
+        let w = &self.planner.world;
+        let (player_es, energy_es) = (w.read::<PlayerControlled>(), w.read::<Energy>());
+        let ready = (&player_es, &energy_es).iter().all(|(_, e)| e.is_ready());
+        ready
+    }
+
+    pub fn time_mode(&self) -> scheduler::TimeMode {
+        self.scheduler.mode()
+    }
+
+    pub fn set_time_mode(&mut self, mode: scheduler::TimeMode) {
+        self.scheduler.set_mode(mode);
+    }
+
+    pub fn turn(&self) -> u64 {
+        self.scheduler.turn()
+    }
+
+    /// The seed of monsters' decisions. The same seed and commands give the same game.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Replaces the prefabs which `spawn` creates with the ones in the file.
+    pub fn load_prefabs(&mut self, path: &str) -> Result<(), cfg::CfgError> {
+        self.prefabs = try!(cfg::prefabs::load(path));
+        Ok(())
+    }
+
+    pub fn prefabs(&self) -> &cfg::prefabs::PrefabSetCfg {
+        &self.prefabs
+    }
+
+    /// Creates an entity of the prefab at the position. Items are created lying on the floor.
+    pub fn spawn(&self, prefab: &str, (x, y, z): (u32, u32, u32)) -> Result<specs::Entity, cfg::CfgError> {
+        use components::{Name, Visible, PlayerControlled, BlocksMovement, Viewer, Energy, Health,
+                         CombatStats, Ai, Script, Item, Inventory, LightSource};
+
+        let c = try!(self.prefabs.resolve(prefab));
+        let mut b = self.create_at((x, y, z));
+
+        if let Some(ref name) = c.name {
+            b = b.with(Name::new(name));
+        }
+        // items are drawn with their own glyph
+        if let Some(glyph) = c.glyph.or(c.item.as_ref().map(|i| i.glyph)) {
+            b = b.with(Visible { mark: glyph });
+        }
+        if let Some(true) = c.player {
+            b = b.with(PlayerControlled::default());
+        }
+        if let Some(true) = c.blocks_movement {
+            b = b.with(BlocksMovement::default());
+        }
+        if let Some(radius) = c.sight_radius {
+            b = b.with(Viewer::new(radius));
+        }
+        if let Some(ref en) = c.energy {
+            b = b.with(Energy::new(en.speed));
+        }
+        if let Some(health) = c.health {
+            b = b.with(Health::new(health));
+        }
+        if let Some(ref cs) = c.combat {
+            b = b.with(CombatStats::new(cs.power, cs.defense, cs.accuracy));
+        }
+        if let Some(ref a) = c.ai {
+            b = b.with(Ai::new(try!(ai::behaviours_from_cfg(a))));
+        }
+        if let Some(ref s) = c.script {
+            b = b.with(Script::new(s));
+        }
+        if let Some(ref item) = c.item {
+            b = b.with(try!(Item::from_cfg(item)));
+        }
+        if let Some(max_weight) = c.inventory {
+            b = b.with(Inventory::new(max_weight));
+        }
+        if let Some(ref l) = c.light {
+            b = b.with(LightSource::new(l.radius, l.color, l.intensity, l.flicker));
+        }
+
+        Ok(b.build())
+    }
+
+    /// Replaces the map and moves the player's entities to the spawn point of the new level.
+    /// All other entities are removed, except for the items the player carries.
+    ///
+    /// The player's memory of the left level is kept under its name, and restored when a level
+    /// with that name is set again. The level the world was created with has an empty name.
+    pub fn set_level(&mut self, name: &str, level: gen::Level) {
+        use specs::Join;
+        use components::{Position, PlayerControlled, Inventory};
+
+        self.planner.wait();
+
+        if self.recording.take().is_some() {
+            warn!("the recording is stopped, a replay can't recreate the new level");
+        }
+
+        let size = level.map.size();
+        *self.map.lock().unwrap() = level.map;
+
+        {
+            let memory = &mut self.memory.lock().unwrap();
+            let left = mem::replace(&mut **memory, map::MapMemory::new(size));
+            self.level_memories.insert(self.level.clone(), left);
+
+            if let Some(m) = self.level_memories.remove(name) {
+                if m.size() == size {
+                    **memory = m;
+                }
+            }
+            self.level = name.to_owned();
+        }
+
+        let w = &self.planner.world;
+
+        let others: Vec<specs::Entity> = {
+            let (player_es, inventory_es) = (w.read::<PlayerControlled>(), w.read::<Inventory>());
+            let carried: Vec<specs::Entity> = (&inventory_es, &player_es).iter()
+                .flat_map(|(inv, _)| inv.items.clone())
+                .collect();
+
+            w.entities().filter(|e| player_es.get(*e).is_none() && !carried.contains(e)).collect()
+        };
+
+        // only the player's entities stay in the index, the carried items have no position
+        let index = &mut self.spatial.lock().unwrap();
+        index.clear();
+
+        for e in others {
+            w.delete_now(e);
+        }
+
+        let (mut pos_es, player_es) = (w.write::<Position>(), w.read::<PlayerControlled>());
+
+        let (x, y, z) = level.spawn;
+        for e in w.entities().filter(|e| player_es.get(*e).is_some()) {
+            if let Some(pos) = pos_es.get_mut(e) {
+                *pos = Position::new(x, y, z);
+                index.insert(e, (x, y, z));
+            }
+        }
+    }
+
+    /// Queues a command. It's performed as soon as the player is ready to act.
     pub fn send_player_command(&mut self, cmd: PlayerCommand) {
-        match self.player_commands.send(cmd) {
-            Ok(_) => (),
-            Err(e) => panic!("Unhandled error while sending player commands: {:?}", e),
+        self.scheduler.push_command(cmd);
+    }
+
+    /// Returns the results of the commands performed since the last call.
+    pub fn command_reports(&mut self) -> Vec<CommandReport> {
+        self.planner.wait();
+        let mut reports = Vec::new();
+        while let Ok(r) = self.command_reports.try_recv() {
+            reports.push(r);
+        }
+        reports
+    }
+
+    /// Returns the combat events since the last call.
+    pub fn combat_events(&mut self) -> Vec<CombatEvent> {
+        self.planner.wait();
+        let mut events = Vec::new();
+        while let Ok(e) = self.combat_events.try_recv() {
+            events.push(e);
         }
+        events
+    }
+
+    /// Returns `true` if all of the player's entities are dead.
+    pub fn is_player_dead(&self) -> bool {
+        use components::{PlayerControlled, Dead};
+
+        let w = &self.planner.world;
+        let (player_es, dead_es) = (w.read::<PlayerControlled>(), w.read::<Dead>());
+        let players: Vec<specs::Entity> = w.entities().filter(|e| player_es.get(*e).is_some()).collect();
+        !players.is_empty() && players.iter().all(|e| dead_es.get(*e).is_some())
+    }
+
+    /// Returns the items carried by the player, in the order of their indices in item commands.
+    pub fn player_inventory(&self) -> Vec<components::Item> {
+        use specs::Join;
+        use components::{PlayerControlled, Item, Inventory};
+
+        let w = &self.planner.world;
+        let (player_es, inventory_es, item_es) = (w.read::<PlayerControlled>(), w.read::<Inventory>(),
+                                                  w.read::<Item>());
+        let items = (&inventory_es, &player_es).iter()
+            .next()
+            .map_or(Vec::new(), |(inv, _)| inv.items.iter().filter_map(|i| item_es.get(*i).cloned()).collect());
+        items
     }
 
     pub fn last_render(&self) -> &systems::render::RenderedViewHolder {
@@ -103,6 +682,10 @@ impl World {
         &self.render_view
     }
 
+    pub fn camera(&self) -> &systems::camera::CameraHolder {
+        &self.camera
+    }
+
     pub fn map(&self) -> &MapHolder {
         &self.map
     }
@@ -110,4 +693,45 @@ impl World {
     pub fn map_mut(&mut self) -> &mut MapHolder {
         &mut self.map
     }
+
+    /// Returns the map of the current level as the player remembers it.
+    pub fn memory(&self) -> &MapMemoryHolder {
+        &self.memory
+    }
+
+    /// Returns the name of the current level.
+    pub fn level(&self) -> &str {
+        &self.level
+    }
+
+    pub fn terrain(&self) -> &terrain::TerrainHolder {
+        &self.terrain
+    }
+
+    /// Returns the messages for the player.
+    pub fn message_log(&self) -> &messages::MessageLogHolder {
+        &self.log
+    }
+
+    /// Returns the ambient light of the levels and the light of the sources.
+    pub fn lighting(&self) -> &light::LightingHolder {
+        &self.lighting
+    }
+
+    /// Returns the index of the entities by their positions, after the running systems finish.
+    pub fn spatial_index(&mut self) -> &spatial::SpatialIndexHolder {
+        self.planner.wait();
+        &self.spatial
+    }
+
+    /// Returns the scripts of the monsters, items and terrain triggers.
+    pub fn scripts(&self) -> &script::ScriptsHolder {
+        &self.scripts
+    }
+
+    /// The entities and their components. Entities with a `Position` are created with `create_at`
+    /// and deleted with `delete`, so the spatial index stays current.
+    pub fn entities(&self) -> &specs::World {
+        &self.planner.world
+    }
 }
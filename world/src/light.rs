@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Red, green and blue, 1.0 is the full brightness. Bright light can exceed 1.0.
+pub type Color = [f32; 3];
+
+pub const WHITE: Color = [1.0, 1.0, 1.0];
+
+/// Light falling on the cells of the map: the ambient light of each z-level and the light of
+/// the `LightSource`s.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lighting {
+    /// Ambient light of the levels without their own.
+    pub default_ambient: Color,
+    /// Ambient light by z-level.
+    ambient: Vec<Option<Color>>,
+    /// Light of the sources, updated by the lighting system.
+    #[serde(skip_serializing, skip_deserializing)]
+    cells: HashMap<(u32, u32, u32), Color>,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting {
+            default_ambient: WHITE,
+            ambient: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl Lighting {
+    pub fn ambient(&self, z: u32) -> Color {
+        self.ambient.get(z as usize).and_then(|c| *c).unwrap_or(self.default_ambient)
+    }
+
+    pub fn set_ambient(&mut self, z: u32, c: Color) {
+        let z = z as usize;
+        if self.ambient.len() <= z {
+            self.ambient.resize(z + 1, None);
+        }
+        self.ambient[z] = Some(c);
+    }
+
+    /// Removes the light of all sources.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Adds the light of a source to the cell.
+    pub fn add(&mut self, coords: (u32, u32, u32), c: Color) {
+        let l = self.cells.entry(coords).or_insert([0.0, 0.0, 0.0]);
+        for i in 0..3 {
+            l[i] += c[i];
+        }
+    }
+
+    /// Returns the total light of the cell.
+    pub fn light_at(&self, (x, y, z): (u32, u32, u32)) -> Color {
+        let mut l = self.ambient(z);
+        if let Some(c) = self.cells.get(&(x, y, z)) {
+            for i in 0..3 {
+                l[i] += c[i];
+            }
+        }
+        l
+    }
+}
+
+pub type LightingHolder = Arc<Mutex<Lighting>>;
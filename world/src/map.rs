@@ -1,8 +1,10 @@
+use std::fmt;
+use std::str::FromStr;
 use std::ops::{Index, IndexMut};
 
 pub type Cell = u8;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     size: (u32, u32, u32),
     data: Vec<Cell>,
@@ -59,23 +61,365 @@ impl Map {
     }
 }
 
-pub fn load_from_csv(path: &str, size: (u32, u32, u32)) -> Map {
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-    use std::str::FromStr;
+/// Remembers the last known state of the map cells seen by the player.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapMemory {
+    size: (u32, u32, u32),
+    data: Vec<Option<Cell>>,
+}
+
+impl MapMemory {
+    pub fn new(size: (u32, u32, u32)) -> Self {
+        MapMemory {
+            size: size,
+            data: vec![None; (size.0 * size.1 * size.2) as usize],
+        }
+    }
+
+    fn idx(&self, coords: (u32, u32, u32)) -> usize {
+        let (sx, sy, _) = self.size;
+        (coords.0 + coords.1 * sx + coords.2 * sx * sy) as usize
+    }
 
-    let f = File::open(path).unwrap();
-    let r = BufReader::new(f);
+    fn contains(&self, (x, y, z): (u32, u32, u32)) -> bool {
+        x < self.size.0 && y < self.size.1 && z < self.size.2
+    }
+
+    /// Remembers the state of the cell. Cells outside of the memory are ignored.
+    pub fn remember(&mut self, coords: (u32, u32, u32), v: Cell) {
+        if self.contains(coords) {
+            let idx = self.idx(coords);
+            self.data[idx] = Some(v);
+        }
+    }
+
+    /// Returns the last known state of the cell or `None` if the cell was never seen.
+    pub fn get(&self, coords: (u32, u32, u32)) -> Option<Cell> {
+        if self.contains(coords) { self.data[self.idx(coords)] } else { None }
+    }
+
+    /// Forgets everything. The memory is resized if it doesn't match the map.
+    pub fn reset(&mut self, size: (u32, u32, u32)) {
+        *self = MapMemory::new(size);
+    }
+
+    pub fn size(&self) -> (u32, u32, u32) {
+        self.size
+    }
+}
+
+#[derive(Debug)]
+pub enum MapLoadErrorKind {
+    IO(String),
+    Parse(String),
+    InvalidSize(String),
+    Unsupported(String),
+}
+
+/// An error occurred while loading a map. `line` and `column` are 1-based, zero means that the
+/// error isn't tied to a specific place in the file.
+#[derive(Debug)]
+pub struct MapLoadError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub kind: MapLoadErrorKind,
+}
+
+impl MapLoadError {
+    fn new(file: &str, (line, column): (usize, usize), kind: MapLoadErrorKind) -> Self {
+        MapLoadError {
+            file: file.to_owned(),
+            line: line,
+            column: column,
+            kind: kind,
+        }
+    }
+}
+
+impl fmt::Display for MapLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {:?}", self.file, self.line, self.column, self.kind)
+    }
+}
+
+/// Parses comma-separated rows of values. Trailing commas and blank lines are allowed.
+/// `origin` is the position of the first character of `s` in the file, used for error reporting.
+/// If `row_len` is specified, each row must contain exactly that many values.
+fn parse_csv<T: FromStr>(file: &str, s: &str, origin: (usize, usize), row_len: Option<usize>)
+                         -> Result<Vec<T>, MapLoadError> {
 
     let mut data = Vec::new();
-    for line in r.lines() {
-        for i in line.unwrap().split(',').map(|s| u8::from_str(s).unwrap()) {
-            data.push(i);
+
+    for (i, line) in s.lines().enumerate() {
+        let line_no = origin.0 + i;
+        let first_col = if i == 0 { origin.1 } else { 1 };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').collect();
+        let mut col = first_col;
+        let mut cnt = 0;
+
+        for (j, field) in fields.iter().enumerate() {
+            let v = field.trim();
+            let v_col = col + field.find(|c: char| !c.is_whitespace()).unwrap_or(field.len());
+            col += field.len() + 1;
+
+            if v.is_empty() {
+                if j == fields.len() - 1 {
+                    // a trailing comma
+                    continue;
+                }
+
+                return Err(MapLoadError::new(file, (line_no, v_col),
+                                             MapLoadErrorKind::Parse("Empty value".to_owned())));
+            }
+
+            match T::from_str(v) {
+                Ok(v) => data.push(v),
+                Err(_) => {
+                    let msg = format!("Invalid value: '{}'", v);
+                    return Err(MapLoadError::new(file, (line_no, v_col), MapLoadErrorKind::Parse(msg)));
+                },
+            }
+
+            cnt += 1;
+        }
+
+        if let Some(n) = row_len {
+            if cnt != n {
+                let msg = format!("Expected {} values in a row, found {}", n, cnt);
+                return Err(MapLoadError::new(file, (line_no, first_col), MapLoadErrorKind::InvalidSize(msg)));
+            }
         }
     }
 
-    Map {
+    Ok(data)
+}
+
+/// Loads a map from a CSV file, one row of the map per line. z-levels follow each other.
+pub fn load_from_csv(path: &str, size: (u32, u32, u32)) -> Result<Map, MapLoadError> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut s = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+        return Err(MapLoadError::new(path, (0, 0), MapLoadErrorKind::IO(format!("{:?}", e))));
+    }
+
+    let (sx, sy, sz) = size;
+    let data = try!(parse_csv::<Cell>(path, &s, (1, 1), Some(sx as usize)));
+
+    let expected = (sx * sy * sz) as usize;
+    if data.len() != expected {
+        let msg = format!("Expected {} rows for size {:?}, found {}", sy * sz, size, data.len() / sx as usize);
+        return Err(MapLoadError::new(path, (s.lines().count(), 1), MapLoadErrorKind::InvalidSize(msg)));
+    }
+
+    Ok(Map {
         size: size,
         data: data,
+    })
+}
+
+/// Tiled stores flip flags in the highest bits of a GID.
+const TMX_FLIP_FLAGS: u32 = 0xE0000000;
+
+/// Loads a map from a Tiled TMX file. The size is taken from the file, each tile layer becomes
+/// a z-level (in the order of appearance).
+pub fn load_from_tmx(path: &str) -> Result<Map, MapLoadError> {
+    use std::fs::File;
+    use std::io::BufReader;
+    use xml::common::Position;
+    use xml::reader::{EventReader, XmlEvent};
+
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(MapLoadError::new(path, (0, 0), MapLoadErrorKind::IO(format!("{:?}", e)))),
+    };
+
+    let mut parser = EventReader::new(BufReader::new(f));
+
+    let mut size = None;
+    let mut first_gids = Vec::new();
+    let mut layers: Vec<(Vec<u32>, (usize, usize))> = Vec::new();
+    let mut data_format: Option<(String, Option<String>)> = None;
+
+    loop {
+        let e = match parser.next() {
+            Ok(e) => e,
+            Err(e) => {
+                let p = e.position();
+                let pos = (p.row as usize + 1, p.column as usize + 1);
+                return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Parse(e.msg().to_owned())));
+            },
+        };
+
+        let pos = {
+            let p = parser.position();
+            (p.row as usize + 1, p.column as usize + 1)
+        };
+
+        match e {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attr = |n: &str| attributes.iter().find(|a| a.name.local_name == n).map(|a| a.value.clone());
+                let attr_u32 = |n: &str| -> Result<Option<u32>, MapLoadError> {
+                    match attr(n) {
+                        Some(v) => match v.parse::<u32>() {
+                            Ok(v) => Ok(Some(v)),
+                            Err(_) => {
+                                let msg = format!("Invalid value of '{}': '{}'", n, v);
+                                Err(MapLoadError::new(path, pos, MapLoadErrorKind::Parse(msg)))
+                            },
+                        },
+                        None => Ok(None),
+                    }
+                };
+                let required = |v: Option<u32>, n: &str| -> Result<u32, MapLoadError> {
+                    v.ok_or_else(|| {
+                        let msg = format!("Missing attribute '{}'", n);
+                        MapLoadError::new(path, pos, MapLoadErrorKind::Parse(msg))
+                    })
+                };
+
+                match name.local_name.as_ref() {
+                    "map" => {
+                        let w = try!(required(try!(attr_u32("width")), "width"));
+                        let h = try!(required(try!(attr_u32("height")), "height"));
+                        size = Some((w, h));
+                    },
+                    "tileset" => first_gids.push(try!(required(try!(attr_u32("firstgid")), "firstgid"))),
+                    "data" => {
+                        let encoding = attr("encoding").unwrap_or_else(|| "xml".to_owned());
+                        data_format = Some((encoding, attr("compression")));
+                        layers.push((Vec::new(), pos));
+                    },
+                    "tile" if data_format.is_some() => {
+                        // plain XML encoding, one element per tile
+                        let gid = try!(attr_u32("gid")).unwrap_or(0);
+                        layers.last_mut().unwrap().0.push(gid);
+                    },
+                    _ => (),
+                }
+            },
+            XmlEvent::Characters(s) => {
+                if let Some((ref encoding, ref compression)) = data_format {
+                    let row_len = size.map(|(w, _)| w as usize);
+                    let gids = match (encoding.as_ref(), compression.as_ref().map(|s| s.as_ref())) {
+                        ("csv", None) => try!(parse_csv::<u32>(path, &s, pos, row_len)),
+                        ("base64", c) => try!(decode_base64_gids(path, pos, &s, c)),
+                        (e, c) => {
+                            let msg = format!("Unsupported layer format: encoding={}, compression={:?}", e, c);
+                            return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Unsupported(msg)));
+                        },
+                    };
+                    layers.last_mut().unwrap().0.extend(gids);
+                }
+            },
+            XmlEvent::EndElement { name } => {
+                if name.local_name == "data" {
+                    data_format = None;
+                }
+            },
+            XmlEvent::EndDocument => break,
+            _ => (),
+        }
     }
+
+    let (sx, sy) = match size {
+        Some(s) => s,
+        None => {
+            let msg = "Missing 'map' element".to_owned();
+            return Err(MapLoadError::new(path, (0, 0), MapLoadErrorKind::Parse(msg)));
+        },
+    };
+
+    let mut data = Vec::with_capacity((sx * sy) as usize * layers.len());
+    for &(ref l, pos) in &layers {
+        if l.len() != (sx * sy) as usize {
+            let msg = format!("Expected {} tiles in a layer, found {}", sx * sy, l.len());
+            return Err(MapLoadError::new(path, pos, MapLoadErrorKind::InvalidSize(msg)));
+        }
+
+        for gid in l {
+            data.push(try!(gid_to_cell(path, pos, &first_gids, *gid)));
+        }
+    }
+
+    Ok(Map {
+        size: (sx, sy, layers.len() as u32),
+        data: data,
+    })
+}
+
+fn decode_base64_gids(path: &str, pos: (usize, usize), s: &str, compression: Option<&str>)
+                      -> Result<Vec<u32>, MapLoadError> {
+
+    use std::io::Read;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let raw = match base64::decode(&s) {
+        Ok(v) => v,
+        Err(e) => return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Parse(format!("{:?}", e)))),
+    };
+
+    let bytes = match compression {
+        None => raw,
+        Some(c) => {
+            let mut buf = Vec::new();
+            let result = match c {
+                "zlib" => ZlibDecoder::new(&raw[..]).read_to_end(&mut buf),
+                "gzip" => GzDecoder::new(&raw[..]).read_to_end(&mut buf),
+                c => {
+                    let msg = format!("Unsupported layer compression: {}", c);
+                    return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Unsupported(msg)));
+                },
+            };
+
+            if let Err(e) = result {
+                return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Parse(format!("{:?}", e))));
+            }
+
+            buf
+        },
+    };
+
+    if bytes.len() % 4 != 0 {
+        let msg = format!("Layer data length must be a multiple of 4, got {}", bytes.len());
+        return Err(MapLoadError::new(path, pos, MapLoadErrorKind::InvalidSize(msg)));
+    }
+
+    // GIDs are stored as little-endian unsigned 32-bit integers
+    Ok(bytes.chunks(4)
+        .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+        .collect())
+}
+
+fn gid_to_cell(path: &str, pos: (usize, usize), first_gids: &[u32], gid: u32) -> Result<Cell, MapLoadError> {
+    let gid = gid & !TMX_FLIP_FLAGS;
+    if gid == 0 {
+        // an empty tile
+        return Ok(0);
+    }
+
+    // the tile belongs to the tileset with the largest firstgid not exceeding its GID
+    let first_gid = match first_gids.iter().cloned().filter(|g| *g <= gid).max() {
+        Some(g) => g,
+        None => {
+            let msg = format!("No tileset found for GID {}", gid);
+            return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Parse(msg)));
+        },
+    };
+
+    let id = gid - first_gid;
+    if id > Cell::max_value() as u32 {
+        let msg = format!("Tile id {} is out of range", id);
+        return Err(MapLoadError::new(path, pos, MapLoadErrorKind::Parse(msg)));
+    }
+
+    Ok(id as Cell)
 }
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    /// Something good happened to the player.
+    Good,
+    /// The player's action failed.
+    Warning,
+    /// The player is in danger.
+    Danger,
+}
+
+impl Severity {
+    /// The default colour of messages of this severity.
+    pub fn color(&self) -> [u8; 4] {
+        match *self {
+            Severity::Info => [200, 200, 200, 255],
+            Severity::Good => [100, 220, 100, 255],
+            Severity::Warning => [230, 200, 80, 255],
+            Severity::Danger => [240, 80, 60, 255],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    pub severity: Severity,
+    pub color: [u8; 4],
+    /// The turn of the last repetition.
+    pub turn: u64,
+    /// How many times the message was repeated in a row.
+    pub count: u32,
+}
+
+impl Message {
+    /// Returns the text with the number of repetitions, e.g. "The rat misses you. x3".
+    pub fn full_text(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Default number of messages kept in the log.
+pub const DEFAULT_CAPACITY: usize = 500;
+
+/// Messages for the player, the newest last. Old messages are dropped when the capacity is
+/// reached.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MessageLog {
+    capacity: usize,
+    messages: VecDeque<Message>,
+}
+
+pub type MessageLogHolder = Arc<Mutex<MessageLog>>;
+
+impl Default for MessageLog {
+    fn default() -> Self {
+        MessageLog::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl MessageLog {
+    pub fn new(capacity: usize) -> Self {
+        MessageLog {
+            capacity: capacity,
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// Adds a message in the colour of its severity.
+    pub fn add(&mut self, text: &str, severity: Severity, turn: u64) {
+        self.add_colored(text, severity, severity.color(), turn);
+    }
+
+    /// Adds a message. If it repeats the last one, only the last one's counter is increased.
+    pub fn add_colored(&mut self, text: &str, severity: Severity, color: [u8; 4], turn: u64) {
+        if let Some(last) = self.messages.back_mut() {
+            if last.text == text && last.severity == severity && last.color == color {
+                last.count += 1;
+                last.turn = turn;
+                return;
+            }
+        }
+
+        if self.messages.len() >= self.capacity {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back(Message {
+            text: text.to_owned(),
+            severity: severity,
+            color: color,
+            turn: turn,
+            count: 1,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Returns the message by its index, 0 is the oldest one.
+    pub fn get(&self, idx: usize) -> Option<&Message> {
+        self.messages.get(idx)
+    }
+
+    /// Returns up to `n` messages, skipping the `skip` newest ones. The newest message is last.
+    pub fn recent(&self, n: usize, skip: usize) -> Vec<&Message> {
+        let end = self.messages.len().saturating_sub(skip);
+        let start = end.saturating_sub(n);
+        (start..end).filter_map(|i| self.messages.get(i)).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
@@ -0,0 +1,335 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32;
+use map;
+use terrain;
+use systems::player_control::ObstacleChecker;
+
+pub type Point = (u32, u32, u32);
+
+/// Defines which cells are considered adjacent on the same z-level. Cells on different z-levels
+/// are adjacent if they're directly above each other, but the cost function decides if such
+/// a move is possible.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Neighbourhood {
+    Four,
+    Eight,
+}
+
+const OFFSETS_FOUR: [(i32, i32, i32); 6] = [
+    (0, -1, 0), (1, 0, 0), (0, 1, 0), (-1, 0, 0),
+    (0, 0, -1), (0, 0, 1),
+];
+
+const OFFSETS_EIGHT: [(i32, i32, i32); 10] = [
+    (0, -1, 0), (1, 0, 0), (0, 1, 0), (-1, 0, 0),
+    (1, -1, 0), (1, 1, 0), (-1, 1, 0), (-1, -1, 0),
+    (0, 0, -1), (0, 0, 1),
+];
+
+impl Neighbourhood {
+    fn offsets(&self) -> &'static [(i32, i32, i32)] {
+        match *self {
+            Neighbourhood::Four => &OFFSETS_FOUR,
+            Neighbourhood::Eight => &OFFSETS_EIGHT,
+        }
+    }
+
+    /// Minimal number of steps between two points, ignoring obstacles.
+    fn distance(&self, a: Point, b: Point) -> u32 {
+        let d = |a: u32, b: u32| if a > b { a - b } else { b - a };
+        let (dx, dy, dz) = (d(a.0, b.0), d(a.1, b.1), d(a.2, b.2));
+
+        match *self {
+            Neighbourhood::Four => dx + dy + dz,
+            Neighbourhood::Eight => ::std::cmp::max(dx, dy) + dz,
+        }
+    }
+}
+
+/// Defines the cost of moving between adjacent cells.
+pub trait PathCost {
+    /// Returns the cost of moving from `from` to the adjacent cell `to` or `None` if the move
+    /// is impossible.
+    fn cost(&self, from: Point, to: Point) -> Option<f32>;
+
+    /// The lowest possible cost of a single move. Used to estimate the remaining distance.
+    fn min_cost(&self) -> f32 {
+        1.0
+    }
+}
+
+/// Every passable cell costs the same. Moves between z-levels are not allowed.
+pub struct ObstacleCost<'a, C: ObstacleChecker + 'a> {
+    checker: &'a C,
+}
+
+impl<'a, C: ObstacleChecker + 'a> ObstacleCost<'a, C> {
+    pub fn new(checker: &'a C) -> Self {
+        ObstacleCost {
+            checker: checker,
+        }
+    }
+}
+
+impl<'a, C: ObstacleChecker + 'a> PathCost for ObstacleCost<'a, C> {
+    fn cost(&self, from: Point, to: Point) -> Option<f32> {
+        if from.2 != to.2 || !self.checker.check(to.0 as i32, to.1 as i32, to.2 as i32) {
+            return None;
+        }
+
+        Some(1.0)
+    }
+}
+
+/// Uses the movement costs of the terrain. Moves between z-levels are not allowed.
+pub struct TerrainCost<'a> {
+    map: &'a map::Map,
+    terrain: &'a terrain::TerrainRegistry,
+    min_cost: f32,
+}
+
+impl<'a> TerrainCost<'a> {
+    pub fn new(map: &'a map::Map, terrain: &'a terrain::TerrainRegistry) -> Self {
+        let min_cost = (0..256)
+            .map(|c| terrain.get(c as map::Cell))
+            .filter(|t| t.passable)
+            .fold(f32::INFINITY, |acc, t| acc.min(t.movement_cost));
+
+        TerrainCost {
+            map: map,
+            terrain: terrain,
+            min_cost: if min_cost.is_finite() { min_cost } else { 1.0 },
+        }
+    }
+}
+
+impl<'a> PathCost for TerrainCost<'a> {
+    fn cost(&self, from: Point, to: Point) -> Option<f32> {
+        if from.2 != to.2 {
+            return None;
+        }
+
+        let t = self.terrain.get(self.map[to]);
+        if !t.passable {
+            return None;
+        }
+
+        Some(t.movement_cost)
+    }
+
+    fn min_cost(&self) -> f32 {
+        self.min_cost
+    }
+}
+
+#[derive(PartialEq)]
+struct State {
+    cost: f32,
+    idx: usize,
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &State) -> Ordering {
+        // reversed, so the binary heap pops the lowest cost first
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &State) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+struct Grid {
+    size: (u32, u32, u32),
+}
+
+impl Grid {
+    fn idx(&self, p: Point) -> usize {
+        let (sx, sy, _) = self.size;
+        (p.0 + p.1 * sx + p.2 * sx * sy) as usize
+    }
+
+    fn point(&self, idx: usize) -> Point {
+        let (sx, sy, _) = self.size;
+        let idx = idx as u32;
+        (idx % sx, (idx / sx) % sy, idx / (sx * sy))
+    }
+
+    fn len(&self) -> usize {
+        (self.size.0 * self.size.1 * self.size.2) as usize
+    }
+
+    fn is_inside(&self, p: Point) -> bool {
+        p.0 < self.size.0 && p.1 < self.size.1 && p.2 < self.size.2
+    }
+
+    fn neighbours<'a>(&'a self, p: Point, n: Neighbourhood) -> Box<Iterator<Item = Point> + 'a> {
+        Box::new(n.offsets().iter()
+            .map(move |&(dx, dy, dz)| (p.0 as i32 + dx, p.1 as i32 + dy, p.2 as i32 + dz))
+            .filter(|&(x, y, z)| x >= 0 && y >= 0 && z >= 0)
+            .map(|(x, y, z)| (x as u32, y as u32, z as u32))
+            .filter(move |&p| self.is_inside(p)))
+    }
+}
+
+/// Finds the shortest path from `start` to `goal` using A*. The returned path doesn't include
+/// `start` and ends with `goal`.
+pub fn find_path<C: PathCost>(cost: &C, size: (u32, u32, u32), start: Point, goal: Point,
+                              n: Neighbourhood) -> Option<Vec<Point>> {
+
+    let grid = Grid { size: size };
+    if !grid.is_inside(start) || !grid.is_inside(goal) {
+        return None;
+    }
+
+    let h = |p: Point| n.distance(p, goal) as f32 * cost.min_cost();
+
+    let mut costs = vec![f32::INFINITY; grid.len()];
+    let mut came_from: Vec<Option<usize>> = vec![None; grid.len()];
+    let mut queue = BinaryHeap::new();
+
+    let start_idx = grid.idx(start);
+    let goal_idx = grid.idx(goal);
+
+    costs[start_idx] = 0.0;
+    queue.push(State { cost: h(start), idx: start_idx });
+
+    while let Some(State { cost: estimate, idx }) = queue.pop() {
+        if idx == goal_idx {
+            let mut path = Vec::new();
+            let mut i = goal_idx;
+            while i != start_idx {
+                path.push(grid.point(i));
+                i = came_from[i].unwrap();
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let p = grid.point(idx);
+        if estimate > costs[idx] + h(p) {
+            // an outdated entry
+            continue;
+        }
+
+        for next in grid.neighbours(p, n) {
+            if let Some(c) = cost.cost(p, next) {
+                let next_idx = grid.idx(next);
+                let next_cost = costs[idx] + c;
+
+                if next_cost < costs[next_idx] {
+                    costs[next_idx] = next_cost;
+                    came_from[next_idx] = Some(idx);
+                    queue.push(State { cost: next_cost + h(next), idx: next_idx });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Distances from every cell to the nearest goal.
+pub struct DijkstraMap {
+    grid: Grid,
+    neighbourhood: Neighbourhood,
+    data: Vec<f32>,
+}
+
+impl DijkstraMap {
+    /// Builds a map of distances to the nearest of `goals`.
+    pub fn new<C: PathCost>(cost: &C, size: (u32, u32, u32), goals: &[Point], n: Neighbourhood) -> Self {
+        let seeds: Vec<(Point, f32)> = goals.iter().map(|&g| (g, 0.0)).collect();
+        DijkstraMap::from_seeds(cost, size, &seeds, n)
+    }
+
+    fn from_seeds<C: PathCost>(cost: &C, size: (u32, u32, u32), seeds: &[(Point, f32)], n: Neighbourhood) -> Self {
+        let grid = Grid { size: size };
+        let mut data = vec![f32::INFINITY; grid.len()];
+        let mut queue = BinaryHeap::new();
+
+        for &(p, v) in seeds {
+            if !grid.is_inside(p) {
+                continue;
+            }
+
+            let idx = grid.idx(p);
+            if v < data[idx] {
+                data[idx] = v;
+                queue.push(State { cost: v, idx: idx });
+            }
+        }
+
+        while let Some(State { cost: d, idx }) = queue.pop() {
+            if d > data[idx] {
+                continue;
+            }
+
+            let p = grid.point(idx);
+            for next in grid.neighbours(p, n) {
+                // distances are measured towards the goals, so the cost is of the reverse move
+                if let Some(c) = cost.cost(next, p) {
+                    let next_idx = grid.idx(next);
+                    if d + c < data[next_idx] {
+                        data[next_idx] = d + c;
+                        queue.push(State { cost: d + c, idx: next_idx });
+                    }
+                }
+            }
+        }
+
+        DijkstraMap {
+            grid: grid,
+            neighbourhood: n,
+            data: data,
+        }
+    }
+
+    /// Builds a map for running away from the goals of this map. `coef` should be below -1.0,
+    /// lower values make the fleeing more "cowardly", higher - make it prefer to slip past
+    /// the goals to reach more distant places.
+    pub fn flee<C: PathCost>(&self, cost: &C, coef: f32) -> Self {
+        let seeds: Vec<(Point, f32)> = self.data.iter()
+            .enumerate()
+            .filter(|&(_, v)| v.is_finite())
+            .map(|(i, v)| (self.grid.point(i), v * coef))
+            .collect();
+
+        DijkstraMap::from_seeds(cost, self.grid.size, &seeds, self.neighbourhood)
+    }
+
+    /// Returns the distance to the nearest goal or `None` if no goal is reachable.
+    pub fn get(&self, p: Point) -> Option<f32> {
+        if !self.grid.is_inside(p) {
+            return None;
+        }
+
+        let v = self.data[self.grid.idx(p)];
+        if v.is_finite() { Some(v) } else { None }
+    }
+
+    /// Returns the reachable adjacent cell with the lowest value, i.e. the next step towards
+    /// the nearest goal. Returns `None` if there is no better cell than `p`.
+    pub fn next_step<C: PathCost>(&self, cost: &C, p: Point) -> Option<Point> {
+        let mut best = (p, match self.get(p) { Some(v) => v, None => return None });
+
+        for next in self.grid.neighbours(p, self.neighbourhood) {
+            if cost.cost(p, next).is_none() {
+                continue;
+            }
+
+            if let Some(v) = self.get(next) {
+                if v < best.1 {
+                    best = (next, v);
+                }
+            }
+        }
+
+        if best.0 == p { None } else { Some(best.0) }
+    }
+}
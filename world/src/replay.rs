@@ -0,0 +1,185 @@
+use serde_json;
+use save;
+use PlayerCommand;
+
+/// Incremented on every incompatible change of the format.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// A checkpoint is recorded before every this many commands.
+pub const CHECKPOINT_INTERVAL: usize = 10;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    IO(String),
+    Parse(String),
+    /// The world of the map source can't be created.
+    Source(String),
+    /// The file was written by an incompatible version, contains the file's version.
+    Version(u32),
+    /// The replayed game differs from the recorded one.
+    Diverged(String),
+}
+
+impl From<serde_json::error::Error> for ReplayError {
+    fn from(e: serde_json::error::Error) -> Self {
+        ReplayError::Parse(format!("{:?}", e))
+    }
+}
+
+impl From<::std::io::Error> for ReplayError {
+    fn from(e: ::std::io::Error) -> Self {
+        ReplayError::IO(format!("{:?}", e))
+    }
+}
+
+impl From<save::SaveError> for ReplayError {
+    fn from(e: save::SaveError) -> Self {
+        ReplayError::Source(format!("{:?}", e))
+    }
+}
+
+/// Where the recorded game starts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MapSource {
+    /// The world created by `World::default`.
+    Default,
+    /// A game saved with `World::save`, contains the path.
+    Save(String),
+}
+
+/// A command with the turn it was performed in.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    pub turn: u64,
+    pub command: PlayerCommand,
+}
+
+/// Hash of the world's state before the command with the index, see `World::state_hash`. The
+/// index past the last command is the state at the end of the recording.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub command: usize,
+    pub hash: u64,
+}
+
+/// A recorded game. The same seed, map source and commands give the same game, so it can be
+/// replayed to reproduce a bug.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub source: MapSource,
+    pub commands: Vec<RecordedCommand>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, source: MapSource) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed: seed,
+            source: source,
+            commands: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if a checkpoint should be recorded before the next command.
+    pub fn needs_checkpoint(&self) -> bool {
+        self.commands.len() % CHECKPOINT_INTERVAL == 0
+    }
+
+    /// Adds a command. The hash of the state before it is needed if `needs_checkpoint`.
+    pub fn record(&mut self, turn: u64, command: PlayerCommand, hash: Option<u64>) {
+        if let Some(hash) = hash {
+            self.checkpoints.push(Checkpoint { command: self.commands.len(), hash: hash });
+        }
+
+        self.commands.push(RecordedCommand {
+            turn: turn,
+            command: command,
+        });
+    }
+
+    /// Adds the checkpoint of the final state.
+    pub fn finish(&mut self, hash: u64) {
+        let n = self.commands.len();
+        if self.checkpoints.last().map_or(true, |c| c.command != n) {
+            self.checkpoints.push(Checkpoint { command: n, hash: hash });
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ReplayError> {
+        use std::fs::File;
+        use std::io::Write;
+
+        let s = try!(serde_json::to_string(self));
+        let mut f = try!(File::create(path));
+        try!(f.write_all(s.as_bytes()));
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Replay, ReplayError> {
+        use std::fs::File;
+        use std::io::Read;
+
+        let mut f = try!(File::open(path));
+        let mut buf = String::new();
+        try!(f.read_to_string(&mut buf));
+
+        let replay: Replay = try!(serde_json::from_str(&buf));
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+
+    /// Creates the world the recorded game starts in.
+    pub fn start(&self) -> Result<::World, ReplayError> {
+        let mut world = match self.source {
+            MapSource::Default => ::World::default(),
+            MapSource::Save(ref path) => try!(::World::load(path)),
+        };
+        world.set_seed(self.seed);
+        Ok(world)
+    }
+
+    fn check(&self, world: &mut ::World, command: usize) -> Result<(), ReplayError> {
+        for c in self.checkpoints.iter().filter(|c| c.command == command) {
+            let hash = world.state_hash();
+            if hash != c.hash {
+                return Err(ReplayError::Diverged(format!("state before command {} (turn {}) is {:x}, expected {:x}",
+                                                         command, world.turn(), hash, c.hash)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Feeds the commands to the world in their turns and verifies the checkpoints. Returns the
+    /// world at the end of the game.
+    pub fn play(&self) -> Result<::World, ReplayError> {
+        let mut world = try!(self.start());
+
+        // the world does the first tick before any command, as the UIs do
+        world.tick();
+
+        for (i, c) in self.commands.iter().enumerate() {
+            // turns passed without the player's action in the real-time mode
+            while world.turn() < c.turn {
+                world.pass_turn();
+            }
+
+            if world.turn() != c.turn {
+                return Err(ReplayError::Diverged(format!("command {} was performed in turn {}, now it's turn {}",
+                                                         i, c.turn, world.turn())));
+            }
+
+            try!(self.check(&mut world, i));
+            world.send_player_command(c.command);
+            world.tick();
+        }
+
+        try!(self.check(&mut world, self.commands.len()));
+        Ok(world)
+    }
+}
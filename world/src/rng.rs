@@ -0,0 +1,76 @@
+/// A small deterministic random number generator (xorshift64*). Unlike `rand`'s generators its
+/// output is guaranteed to stay the same, so the seeds can be stored and reused.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+/// Scrambles the seed, so similar seeds give unrelated states. It's a bijection: different
+/// seeds always give different states.
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // the state must never be zero, only one seed gives it
+        let state = match splitmix64(seed) {
+            0 => 0x9E3779B97F4A7C15,
+            s => s,
+        };
+
+        Rng {
+            state: state,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a number in `[low, high)`.
+    pub fn gen_range(&mut self, low: u32, high: u32) -> u32 {
+        assert!(low < high, "Invalid range: [{}, {})", low, high);
+        low + self.next_u32() % (high - low)
+    }
+
+    /// Returns a number in `[0.0, 1.0)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Returns `true` with the probability `p`.
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+
+    /// Returns a random element of the slice.
+    pub fn choose<'a, T>(&mut self, v: &'a [T]) -> Option<&'a T> {
+        if v.is_empty() {
+            return None;
+        }
+
+        let i = self.gen_range(0, v.len() as u32) as usize;
+        Some(&v[i])
+    }
+}
+
+/// Derives a seed for the random decisions of an entity in a turn. Different `stream`s give
+/// independent sequences for the same entity and turn.
+pub fn entity_seed(seed: u64, turn: u64, entity_id: u32, stream: u64) -> u64 {
+    seed.wrapping_add(turn.wrapping_mul(0x9E3779B97F4A7C15))
+        .wrapping_add((entity_id as u64).wrapping_mul(0xC2B2AE3D27D4EB4F))
+        .wrapping_add(stream.wrapping_mul(0x165667B19E3779F9))
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use specs;
+use serde_json;
+use map;
+use terrain;
+use messages;
+use light;
+use components::{Position, Visible, PlayerControlled, Viewer, Energy, BlocksMovement, Health, Ai,
+                 CombatStats, Dead, Name, Item, Inventory, LightSource, Script, StableId};
+use systems::render::View;
+use spatial::SpatialIndex;
+
+/// Incremented on every incompatible change of the format.
+pub const SAVE_VERSION: u32 = 11;
+
+#[derive(Debug)]
+pub enum SaveError {
+    IO(String),
+    Parse(String),
+    Terrain(String),
+    /// The scripts or the prefabs the game refers to can't be loaded.
+    Assets(String),
+    /// The file was saved by an incompatible version, contains the file's version.
+    Version(u32),
+}
+
+impl From<serde_json::error::Error> for SaveError {
+    fn from(e: serde_json::error::Error) -> Self {
+        SaveError::Parse(format!("{:?}", e))
+    }
+}
+
+impl From<::std::io::Error> for SaveError {
+    fn from(e: ::std::io::Error) -> Self {
+        SaveError::IO(format!("{:?}", e))
+    }
+}
+
+/// Components of a single entity. Every registered component must be present here.
+#[derive(Serialize, Deserialize)]
+struct EntityData {
+    position: Option<Position>,
+    visible: Option<Visible>,
+    player_controlled: Option<PlayerControlled>,
+    viewer: Option<Viewer>,
+    energy: Option<Energy>,
+    blocks_movement: Option<BlocksMovement>,
+    health: Option<Health>,
+    ai: Option<Ai>,
+    combat_stats: Option<CombatStats>,
+    dead: Option<Dead>,
+    name: Option<Name>,
+    item: Option<Item>,
+    inventory: Option<InventoryData>,
+    light_source: Option<LightSource>,
+    script: Option<Script>,
+    id: Option<StableId>,
+}
+
+/// An `Inventory` refers to the items by their indices in the saved entities.
+#[derive(Serialize, Deserialize)]
+struct InventoryData {
+    items: Vec<usize>,
+    max_weight: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    turn: u64,
+    seed: u64,
+    /// The `StableId` of the next created entity.
+    next_id: u32,
+    map: map::Map,
+    memory: map::MapMemory,
+    level: String,
+    level_memories: HashMap<String, map::MapMemory>,
+    view: View,
+    log: messages::MessageLog,
+    lighting: light::Lighting,
+    entities: Vec<EntityData>,
+}
+
+/// The part of the game which is the same when it's replayed: the view and the map memory depend
+/// on the UI, the light flickers with the frames.
+#[derive(Serialize)]
+struct StateData<'a> {
+    turn: u64,
+    seed: u64,
+    map: &'a map::Map,
+    entities: Vec<EntityData>,
+}
+
+/// Only the version is read first, so files of other versions are reported properly.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+fn collect_entities(w: &specs::World) -> Vec<EntityData> {
+    let (pos_es, vis_es, player_es, viewer_es) = (w.read::<Position>(), w.read::<Visible>(),
+                                                  w.read::<PlayerControlled>(), w.read::<Viewer>());
+    let (energy_es, blocking_es, health_es, ai_es) = (w.read::<Energy>(), w.read::<BlocksMovement>(),
+                                                      w.read::<Health>(), w.read::<Ai>());
+    let (stats_es, dead_es, name_es) = (w.read::<CombatStats>(), w.read::<Dead>(), w.read::<Name>());
+    let (item_es, inventory_es, light_es) = (w.read::<Item>(), w.read::<Inventory>(), w.read::<LightSource>());
+    let (script_es, id_es) = (w.read::<Script>(), w.read::<StableId>());
+
+    let entities: Vec<specs::Entity> = w.entities().collect();
+    let index_of = |e: &specs::Entity| entities.iter().position(|x| x == e);
+
+    entities.iter().map(|&e| {
+        EntityData {
+            position: pos_es.get(e).cloned(),
+            visible: vis_es.get(e).cloned(),
+            player_controlled: player_es.get(e).cloned(),
+            viewer: viewer_es.get(e).cloned(),
+            energy: energy_es.get(e).cloned(),
+            blocks_movement: blocking_es.get(e).cloned(),
+            health: health_es.get(e).cloned(),
+            ai: ai_es.get(e).cloned(),
+            combat_stats: stats_es.get(e).cloned(),
+            dead: dead_es.get(e).cloned(),
+            name: name_es.get(e).cloned(),
+            item: item_es.get(e).cloned(),
+            inventory: inventory_es.get(e).map(|inv| {
+                InventoryData {
+                    items: inv.items.iter().filter_map(&index_of).collect(),
+                    max_weight: inv.max_weight,
+                }
+            }),
+            light_source: light_es.get(e).cloned(),
+            script: script_es.get(e).cloned(),
+            id: id_es.get(e).cloned(),
+        }
+    }).collect()
+}
+
+fn create_entities(w: &specs::World, index: &mut SpatialIndex, entities: Vec<EntityData>) {
+    let mut created = Vec::new();
+    let mut inventories = Vec::new();
+
+    for data in entities {
+        let mut b = w.create_now();
+        let mut point = None;
+
+        if let Some(c) = data.position {
+            point = Some((c.x, c.y, c.z));
+            b = b.with(c);
+        }
+        if let Some(c) = data.visible {
+            b = b.with(c);
+        }
+        if let Some(c) = data.player_controlled {
+            b = b.with(c);
+        }
+        if let Some(c) = data.viewer {
+            b = b.with(c);
+        }
+        if let Some(c) = data.energy {
+            b = b.with(c);
+        }
+        if let Some(c) = data.blocks_movement {
+            b = b.with(c);
+        }
+        if let Some(c) = data.health {
+            b = b.with(c);
+        }
+        if let Some(c) = data.ai {
+            b = b.with(c);
+        }
+        if let Some(c) = data.combat_stats {
+            b = b.with(c);
+        }
+        if let Some(c) = data.dead {
+            b = b.with(c);
+        }
+        if let Some(c) = data.name {
+            b = b.with(c);
+        }
+        if let Some(c) = data.item {
+            b = b.with(c);
+        }
+        if let Some(c) = data.light_source {
+            b = b.with(c);
+        }
+        if let Some(c) = data.script {
+            b = b.with(c);
+        }
+        if let Some(c) = data.id {
+            b = b.with(c);
+        }
+
+        let e = b.build();
+        created.push(e);
+
+        if let Some(p) = point {
+            index.insert(e, p);
+        }
+
+        if let Some(inv) = data.inventory {
+            inventories.push((e, inv));
+        }
+    }
+
+    // the items are created by now
+    let mut inventory_es = w.write::<Inventory>();
+    for (e, data) in inventories {
+        let mut inv = Inventory::new(data.max_weight);
+        inv.items = data.items.iter().filter_map(|&i| created.get(i).cloned()).collect();
+        inventory_es.insert(e, inv);
+    }
+}
+
+pub fn save(world: &::World, path: &str) -> Result<(), SaveError> {
+    use std::fs::File;
+    use std::io::Write;
+
+    let data = SaveFile {
+        version: SAVE_VERSION,
+        turn: world.turn(),
+        seed: world.seed(),
+        next_id: world.next_id.get(),
+        map: world.map().lock().unwrap().clone(),
+        memory: world.memory().lock().unwrap().clone(),
+        level: world.level.clone(),
+        level_memories: world.level_memories.clone(),
+        view: world.render_view().lock().unwrap().clone(),
+        log: world.message_log().lock().unwrap().clone(),
+        lighting: world.lighting().lock().unwrap().clone(),
+        entities: collect_entities(world.entities()),
+    };
+
+    let s = try!(serde_json::to_string(&data));
+    let mut f = try!(File::create(path));
+    try!(f.write_all(s.as_bytes()));
+    Ok(())
+}
+
+/// 64-bit FNV-1a, stable across platforms and builds unlike the std hashers.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+/// Hashes the turn, the seed, the map and all entities.
+pub fn state_hash(world: &::World) -> u64 {
+    let map = world.map().lock().unwrap();
+    let data = StateData {
+        turn: world.turn(),
+        seed: world.seed(),
+        map: &map,
+        entities: collect_entities(world.entities()),
+    };
+
+    match serde_json::to_string(&data) {
+        Ok(s) => fnv1a(s.as_bytes()),
+        Err(e) => panic!("Unhandled error while serializing the state: {:?}", e),
+    }
+}
+
+pub fn load(path: &str, terrain: terrain::TerrainHolder) -> Result<::World, SaveError> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut f = try!(File::open(path));
+    let mut buf = String::new();
+    try!(f.read_to_string(&mut buf));
+
+    let header: SaveHeader = try!(serde_json::from_str(&buf));
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::Version(header.version));
+    }
+
+    let data: SaveFile = try!(serde_json::from_str(&buf));
+
+    let mut world = ::World::new(data.map, terrain);
+    world.scheduler.set_turn(data.turn);
+    world.set_seed(data.seed);
+    world.next_id.set(data.next_id);
+    *world.memory().lock().unwrap() = data.memory;
+    world.level = data.level;
+    world.level_memories = data.level_memories;
+    *world.render_view().lock().unwrap() = data.view;
+    *world.message_log().lock().unwrap() = data.log;
+    *world.lighting().lock().unwrap() = data.lighting;
+    create_entities(world.entities(), &mut world.spatial.lock().unwrap(), data.entities);
+
+    Ok(world)
+}
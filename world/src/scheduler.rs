@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use PlayerCommand;
+
+/// Energy needed to perform an action.
+pub const ACTION_COST: i32 = 100;
+
+/// Speed of an average actor: it acts once per turn.
+pub const NORMAL_SPEED: u32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeMode {
+    /// Time passes only when the player acts.
+    TurnBased,
+    /// Turns pass with a fixed rate, whether the player acts or not.
+    RealTime { turns_per_second: f64 },
+}
+
+/// What the world should do next.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    /// The player performs an action.
+    Act(PlayerCommand),
+    /// A turn passes: actors gain energy and act.
+    Advance,
+    /// Nothing happens, the world is only redrawn.
+    Idle,
+}
+
+/// Decides when the player acts and when the turns pass.
+pub struct Scheduler {
+    mode: TimeMode,
+    turn: u64,
+    /// Time accumulated towards the next turn in the real-time mode.
+    accumulator: f64,
+    commands: VecDeque<PlayerCommand>,
+}
+
+impl Scheduler {
+    pub fn new(mode: TimeMode) -> Self {
+        Scheduler {
+            mode: mode,
+            turn: 0,
+            accumulator: 0.0,
+            commands: VecDeque::new(),
+        }
+    }
+
+    pub fn mode(&self) -> TimeMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: TimeMode) {
+        self.mode = mode;
+        self.accumulator = 0.0;
+    }
+
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    pub fn set_turn(&mut self, turn: u64) {
+        self.turn = turn;
+    }
+
+    /// Queues a command until the player is ready to act.
+    pub fn push_command(&mut self, cmd: PlayerCommand) {
+        self.commands.push_back(cmd);
+    }
+
+    /// Returns the next step. `dt` is the time passed since the previous call, `player_ready` is
+    /// `true` if the player has enough energy to act.
+    pub fn next_step(&mut self, dt: f64, player_ready: bool) -> Step {
+        if let TimeMode::RealTime { .. } = self.mode {
+            self.accumulator += dt;
+        }
+
+        if player_ready {
+            if let Some(cmd) = self.commands.pop_front() {
+                return Step::Act(cmd);
+            }
+        }
+
+        let advance = match self.mode {
+            TimeMode::TurnBased => !player_ready,
+            TimeMode::RealTime { turns_per_second } => {
+                let turn_length = 1.0 / turns_per_second;
+                if self.accumulator >= turn_length {
+                    self.accumulator -= turn_length;
+                    true
+                } else {
+                    false
+                }
+            },
+        };
+
+        if advance {
+            self.turn += 1;
+            Step::Advance
+        } else {
+            Step::Idle
+        }
+    }
+}
@@ -0,0 +1,749 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// The integers of the scripts.
+pub type INT = i64;
+
+/// Calls nested deeper than this fail, so a recursive script can't overflow the stack.
+const MAX_CALL_DEPTH: usize = 64;
+/// A call fails after evaluating this many expressions.
+const MAX_OPERATIONS: u64 = 100000;
+
+const KEYWORDS: &'static [&'static str] = &["fn", "let", "if", "else", "return", "true", "false"];
+
+/// Longer operators first, so `==` isn't read as two `=`.
+const PUNCTUATION: &'static [&'static str] = &["==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", "[", "]", ",",
+                                               ";", "=", "<", ">", "+", "-", "*", "/", "%", "!"];
+
+/// Binary operators with their precedence, higher binds tighter.
+const BINARY: &'static [(&'static str, u8)] = &[("||", 1), ("&&", 2), ("==", 3), ("!=", 3), ("<", 4), ("<=", 4),
+                                                (">", 4), (">=", 4), ("+", 5), ("-", 5), ("*", 6), ("/", 6),
+                                                ("%", 6)];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(INT),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Unit => "()",
+            Value::Bool(_) => "a boolean",
+            Value::Int(_) => "an integer",
+            Value::Str(_) => "a string",
+            Value::Array(_) => "an array",
+        }
+    }
+
+    pub fn as_int(&self) -> Result<INT, String> {
+        match *self {
+            Value::Int(n) => Ok(n),
+            ref v => Err(format!("expected an integer, found {}", v.type_name())),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, String> {
+        match *self {
+            Value::Str(ref s) => Ok(s),
+            ref v => Err(format!("expected a string, found {}", v.type_name())),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Unit => write!(f, "()"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Str(ref s) => write!(f, "{}", s),
+            Value::Array(ref items) => {
+                try!(write!(f, "["));
+                for (i, v) in items.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", v));
+                }
+                write!(f, "]")
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(INT),
+    Str(String),
+    Punct(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Ident(ref s) => write!(f, "'{}'", s),
+            Token::Int(n) => write!(f, "'{}'", n),
+            Token::Str(ref s) => write!(f, "\"{}\"", s),
+            Token::Punct(p) => write!(f, "'{}'", p),
+            Token::Eof => write!(f, "the end of the script"),
+        }
+    }
+}
+
+/// Splits the source into tokens, each with its line.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            let start = line;
+            i += 2;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some(&'*'), Some(&'/')) => break,
+                    (Some(&'\n'), _) => line += 1,
+                    (Some(_), _) => (),
+                    (None, _) => return Err(format!("line {}: the comment isn't closed", start)),
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_digit(10) {
+            let start = i;
+            while i < chars.len() && chars[i].is_digit(10) {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().cloned().collect();
+            match text.parse() {
+                Ok(n) => tokens.push((Token::Int(n), line)),
+                Err(_) => return Err(format!("line {}: the number {} is too large", line, text)),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().cloned().collect()), line));
+        } else if c == '"' {
+            let start = line;
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some(&'"') => break,
+                    Some(&'\\') => {
+                        let escaped = match chars.get(i + 1) {
+                            Some(&'n') => '\n',
+                            Some(&'t') => '\t',
+                            Some(&'\\') => '\\',
+                            Some(&'"') => '"',
+                            _ => return Err(format!("line {}: unknown escape sequence", line)),
+                        };
+                        s.push(escaped);
+                        i += 1;
+                    },
+                    Some(&c) => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        s.push(c);
+                    },
+                    None => return Err(format!("line {}: the string isn't closed", start)),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push((Token::Str(s), start));
+        } else {
+            let p = PUNCTUATION.iter()
+                .cloned()
+                .find(|p| p.chars().enumerate().all(|(k, pc)| chars.get(i + k) == Some(&pc)));
+            match p {
+                Some(p) => {
+                    tokens.push((Token::Punct(p), line));
+                    i += p.len();
+                },
+                None => return Err(format!("line {}: unexpected character '{}'", line, c)),
+            }
+        }
+    }
+
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Unit,
+    Bool(bool),
+    Int(INT),
+    Str(String),
+    Array(Vec<Expr>),
+    /// The name and the line of a variable.
+    Var(String, usize),
+    Index(Box<Expr>, Box<Expr>, usize),
+    Call(String, Vec<Expr>, usize),
+    Unary(&'static str, Box<Expr>, usize),
+    Binary(&'static str, Box<Expr>, Box<Expr>, usize),
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Let(String, Expr),
+    Assign(String, Expr, usize),
+    Expr(Expr),
+    /// The condition, the statements of both branches and the line. An `else if` is an `If`
+    /// in the else branch.
+    If(Expr, Vec<Stmt>, Vec<Stmt>, usize),
+    Return(Option<Expr>),
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+}
+
+/// A compiled script, the functions it defines.
+#[derive(Debug)]
+pub struct Ast {
+    functions: HashMap<String, Function>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn advance(&mut self) {
+        // the end stays the current token
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, String> {
+        Err(format!("line {}: expected {}, found {}", self.line(), expected, self.peek()))
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        match *self.peek() {
+            Token::Punct(q) => q == p,
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, k: &str) -> bool {
+        match *self.peek() {
+            Token::Ident(ref s) => s == k,
+            _ => false,
+        }
+    }
+
+    /// Skips the punctuation if it's next.
+    fn eat(&mut self, p: &str) -> bool {
+        let found = self.is_punct(p);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), String> {
+        if self.eat(p) {
+            Ok(())
+        } else {
+            self.error(&format!("'{}'", p))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        let name = match *self.peek() {
+            Token::Ident(ref s) if !KEYWORDS.contains(&&s[..]) => s.clone(),
+            _ => return self.error("a name"),
+        };
+        self.advance();
+        Ok(name)
+    }
+
+    fn program(&mut self) -> Result<Ast, String> {
+        let mut functions = HashMap::new();
+
+        while *self.peek() != Token::Eof {
+            if !self.is_keyword("fn") {
+                return self.error("'fn'");
+            }
+            self.advance();
+
+            let line = self.line();
+            let name = try!(self.ident());
+            try!(self.expect("("));
+
+            let mut params = Vec::new();
+            if !self.eat(")") {
+                loop {
+                    params.push(try!(self.ident()));
+                    if self.eat(")") {
+                        break;
+                    }
+                    try!(self.expect(","));
+                }
+            }
+
+            let body = try!(self.block());
+            let f = Function {
+                params: params,
+                body: body,
+            };
+            if functions.insert(name.clone(), f).is_some() {
+                return Err(format!("line {}: the function '{}' is defined twice", line, name));
+            }
+        }
+
+        Ok(Ast { functions: functions })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        try!(self.expect("{"));
+
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if *self.peek() == Token::Eof {
+                return self.error("'}'");
+            }
+            stmts.push(try!(self.statement()));
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        if self.is_keyword("if") {
+            return self.if_statement();
+        }
+
+        let line = self.line();
+        let stmt = if self.is_keyword("let") {
+            self.advance();
+            let name = try!(self.ident());
+            try!(self.expect("="));
+            Stmt::Let(name, try!(self.expr()))
+        } else if self.is_keyword("return") {
+            self.advance();
+            if self.is_punct(";") || self.is_punct("}") {
+                Stmt::Return(None)
+            } else {
+                Stmt::Return(Some(try!(self.expr())))
+            }
+        } else {
+            let e = try!(self.expr());
+            if self.eat("=") {
+                match e {
+                    Expr::Var(name, _) => Stmt::Assign(name, try!(self.expr()), line),
+                    _ => return Err(format!("line {}: only variables can be assigned to", line)),
+                }
+            } else {
+                Stmt::Expr(e)
+            }
+        };
+
+        // the semicolon can be left out after the last statement of a block
+        if !self.is_punct("}") {
+            try!(self.expect(";"));
+        }
+        Ok(stmt)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, String> {
+        let line = self.line();
+        self.advance();
+
+        let condition = try!(self.expr());
+        let then = try!(self.block());
+        let otherwise = if self.is_keyword("else") {
+            self.advance();
+            if self.is_keyword("if") {
+                vec![try!(self.if_statement())]
+            } else {
+                try!(self.block())
+            }
+        } else {
+            Vec::new()
+        };
+
+        Ok(Stmt::If(condition, then, otherwise, line))
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(1)
+    }
+
+    /// Parses the operators binding at least as tight as `min`.
+    fn binary(&mut self, min: u8) -> Result<Expr, String> {
+        let mut left = try!(self.unary());
+
+        loop {
+            let op = match *self.peek() {
+                Token::Punct(p) => BINARY.iter().cloned().find(|&(op, prec)| op == p && prec >= min),
+                _ => None,
+            };
+            let (op, prec) = match op {
+                Some(op) => op,
+                None => return Ok(left),
+            };
+
+            let line = self.line();
+            self.advance();
+            let right = try!(self.binary(prec + 1));
+            left = Expr::Binary(op, Box::new(left), Box::new(right), line);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let line = self.line();
+        for op in &["-", "!"] {
+            if self.eat(op) {
+                return Ok(Expr::Unary(*op, Box::new(try!(self.unary())), line));
+            }
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut e = try!(self.primary());
+        while self.is_punct("[") {
+            let line = self.line();
+            self.advance();
+            let index = try!(self.expr());
+            try!(self.expect("]"));
+            e = Expr::Index(Box::new(e), Box::new(index), line);
+        }
+        Ok(e)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let line = self.line();
+
+        if self.is_keyword("true") || self.is_keyword("false") {
+            let b = self.is_keyword("true");
+            self.advance();
+            return Ok(Expr::Bool(b));
+        }
+
+        if self.eat("(") {
+            if self.eat(")") {
+                return Ok(Expr::Unit);
+            }
+            let e = try!(self.expr());
+            try!(self.expect(")"));
+            return Ok(e);
+        }
+
+        if self.eat("[") {
+            return Ok(Expr::Array(try!(self.list("]"))));
+        }
+
+        let e = match *self.peek() {
+            Token::Int(n) => Some(Expr::Int(n)),
+            Token::Str(ref s) => Some(Expr::Str(s.clone())),
+            _ => None,
+        };
+        if let Some(e) = e {
+            self.advance();
+            return Ok(e);
+        }
+
+        // a variable or a call
+        let name = try!(self.ident());
+        if self.eat("(") {
+            Ok(Expr::Call(name, try!(self.list(")")), line))
+        } else {
+            Ok(Expr::Var(name, line))
+        }
+    }
+
+    /// Parses comma separated expressions up to the closing punctuation.
+    fn list(&mut self, close: &str) -> Result<Vec<Expr>, String> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+
+        loop {
+            items.push(try!(self.expr()));
+            if self.eat(close) {
+                return Ok(items);
+            }
+            try!(self.expect(","));
+        }
+    }
+}
+
+/// A function the scripts can call, it gets the values of the arguments.
+pub type NativeFn = Box<Fn(&[Value]) -> Result<Value, String> + Send>;
+
+/// Compiles and runs the scripts. They're written in the part of Rhai the game needs: functions,
+/// `let`, assignments, `if`/`else`, `return`, integers, booleans, strings, arrays and the calls of
+/// the functions registered by the game. There are no loops, a call fails if it nests or runs too
+/// long.
+///
+/// The functions of the game are registered with the number of their arguments, the values passed
+/// to them are checked by the functions.
+pub struct Engine {
+    functions: HashMap<(String, usize), NativeFn>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine { functions: HashMap::new() }
+    }
+
+    /// Lets the scripts call the function with the name and the number of arguments.
+    pub fn register<F>(&mut self, name: &str, arity: usize, f: F)
+        where F: Fn(&[Value]) -> Result<Value, String> + Send + 'static {
+
+        self.functions.insert((name.to_owned(), arity), Box::new(f));
+    }
+
+    pub fn compile(&self, source: &str) -> Result<Ast, String> {
+        let mut parser = Parser {
+            tokens: try!(tokenize(source)),
+            pos: 0,
+        };
+        parser.program()
+    }
+
+    /// Calls the function the script defines.
+    pub fn call_fn(&self, ast: &Ast, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        if !ast.functions.contains_key(name) {
+            return Err(format!("the script has no function '{}'", name));
+        }
+
+        let mut run = Run {
+            engine: self,
+            ast: ast,
+            operations: 0,
+        };
+        run.call(name, args, 0, 0)
+    }
+}
+
+enum Flow {
+    Normal,
+    Return(Value),
+}
+
+/// The variables visible at a point of the script, the innermost last.
+type Scope = Vec<(String, Value)>;
+
+/// A call of a script function with the functions it calls in turn.
+struct Run<'a> {
+    engine: &'a Engine,
+    ast: &'a Ast,
+    operations: u64,
+}
+
+impl<'a> Run<'a> {
+    fn call(&mut self, name: &str, args: Vec<Value>, depth: usize, line: usize) -> Result<Value, String> {
+        let ast = self.ast;
+        if let Some(f) = ast.functions.get(name) {
+            if f.params.len() != args.len() {
+                return Err(format!("line {}: '{}' takes {} arguments, not {}", line, name, f.params.len(),
+                                   args.len()));
+            }
+            if depth >= MAX_CALL_DEPTH {
+                return Err(format!("line {}: the calls are nested too deep", line));
+            }
+
+            let mut scope: Scope = f.params.iter().cloned().zip(args).collect();
+            return match try!(self.block(&f.body, &mut scope, depth + 1)) {
+                Flow::Return(v) => Ok(v),
+                Flow::Normal => Ok(Value::Unit),
+            };
+        }
+
+        match self.engine.functions.get(&(name.to_owned(), args.len())) {
+            Some(f) => f(&args).map_err(|e| format!("line {}: {}: {}", line, name, e)),
+            None => Err(format!("line {}: there's no function '{}' with {} arguments", line, name, args.len())),
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt], scope: &mut Scope, depth: usize) -> Result<Flow, String> {
+        // the variables declared in the block go out of scope at its end
+        let len = scope.len();
+
+        let mut flow = Flow::Normal;
+        for s in stmts {
+            flow = try!(self.statement(s, scope, depth));
+            if let Flow::Return(_) = flow {
+                break;
+            }
+        }
+
+        scope.truncate(len);
+        Ok(flow)
+    }
+
+    fn statement(&mut self, s: &Stmt, scope: &mut Scope, depth: usize) -> Result<Flow, String> {
+        match *s {
+            Stmt::Let(ref name, ref e) => {
+                let v = try!(self.eval(e, scope, depth));
+                scope.push((name.clone(), v));
+            },
+            Stmt::Assign(ref name, ref e, line) => {
+                let v = try!(self.eval(e, scope, depth));
+                match scope.iter_mut().rev().find(|var| var.0 == *name) {
+                    Some(var) => var.1 = v,
+                    None => return Err(format!("line {}: there's no variable '{}'", line, name)),
+                }
+            },
+            Stmt::Expr(ref e) => {
+                try!(self.eval(e, scope, depth));
+            },
+            Stmt::If(ref condition, ref then, ref otherwise, line) => {
+                let c = try!(self.eval(condition, scope, depth));
+                let branch = if try!(boolean(c, line)) { then } else { otherwise };
+                return self.block(branch, scope, depth);
+            },
+            Stmt::Return(ref e) => {
+                let v = match *e {
+                    Some(ref e) => try!(self.eval(e, scope, depth)),
+                    None => Value::Unit,
+                };
+                return Ok(Flow::Return(v));
+            },
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn eval(&mut self, e: &Expr, scope: &mut Scope, depth: usize) -> Result<Value, String> {
+        self.operations += 1;
+        if self.operations > MAX_OPERATIONS {
+            return Err("the script runs too long".to_owned());
+        }
+
+        match *e {
+            Expr::Unit => Ok(Value::Unit),
+            Expr::Bool(b) => Ok(Value::Bool(b)),
+            Expr::Int(n) => Ok(Value::Int(n)),
+            Expr::Str(ref s) => Ok(Value::Str(s.clone())),
+            Expr::Array(ref items) => {
+                let mut values = Vec::new();
+                for i in items {
+                    values.push(try!(self.eval(i, scope, depth)));
+                }
+                Ok(Value::Array(values))
+            },
+            Expr::Var(ref name, line) => match scope.iter().rev().find(|var| var.0 == *name) {
+                Some(var) => Ok(var.1.clone()),
+                None => Err(format!("line {}: there's no variable '{}'", line, name)),
+            },
+            Expr::Index(ref array, ref index, line) => {
+                let array = try!(self.eval(array, scope, depth));
+                let index = try!(self.eval(index, scope, depth));
+                match (array, index) {
+                    (Value::Array(mut items), Value::Int(i)) => {
+                        if i < 0 || i as usize >= items.len() {
+                            return Err(format!("line {}: the index {} is out of bounds", line, i));
+                        }
+                        Ok(items.swap_remove(i as usize))
+                    },
+                    (a, i) => Err(format!("line {}: can't index {} with {}", line, a.type_name(), i.type_name())),
+                }
+            },
+            Expr::Call(ref name, ref args, line) => {
+                let mut values = Vec::new();
+                for a in args {
+                    values.push(try!(self.eval(a, scope, depth)));
+                }
+                self.call(name, values, depth, line)
+            },
+            Expr::Unary(op, ref e, line) => {
+                match (op, try!(self.eval(e, scope, depth))) {
+                    ("-", Value::Int(n)) => n.checked_neg()
+                        .map(Value::Int)
+                        .ok_or_else(|| format!("line {}: arithmetic overflow", line)),
+                    ("!", Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (op, v) => Err(format!("line {}: can't apply '{}' to {}", line, op, v.type_name())),
+                }
+            },
+            // the right side is only evaluated if it decides the result
+            Expr::Binary(op @ "&&", ref l, ref r, line) | Expr::Binary(op @ "||", ref l, ref r, line) => {
+                let l = try!(boolean(try!(self.eval(l, scope, depth)), line));
+                if l == (op == "||") {
+                    return Ok(Value::Bool(l));
+                }
+                Ok(Value::Bool(try!(boolean(try!(self.eval(r, scope, depth)), line))))
+            },
+            Expr::Binary(op, ref l, ref r, line) => {
+                let l = try!(self.eval(l, scope, depth));
+                let r = try!(self.eval(r, scope, depth));
+                binary(op, l, r, line)
+            },
+        }
+    }
+}
+
+fn boolean(v: Value, line: usize) -> Result<bool, String> {
+    match v {
+        Value::Bool(b) => Ok(b),
+        v => Err(format!("line {}: expected a boolean, found {}", line, v.type_name())),
+    }
+}
+
+fn binary(op: &str, l: Value, r: Value, line: usize) -> Result<Value, String> {
+    match (op, l, r) {
+        ("==", l, r) => Ok(Value::Bool(l == r)),
+        ("!=", l, r) => Ok(Value::Bool(l != r)),
+        // strings are joined with the text of any value
+        ("+", Value::Str(l), r) => Ok(Value::Str(format!("{}{}", l, r))),
+        ("+", l, Value::Str(r)) => Ok(Value::Str(format!("{}{}", l, r))),
+        (op, Value::Int(l), Value::Int(r)) => {
+            let result = match op {
+                "<" => return Ok(Value::Bool(l < r)),
+                "<=" => return Ok(Value::Bool(l <= r)),
+                ">" => return Ok(Value::Bool(l > r)),
+                ">=" => return Ok(Value::Bool(l >= r)),
+                "/" | "%" if r == 0 => return Err(format!("line {}: division by zero", line)),
+                "+" => l.checked_add(r),
+                "-" => l.checked_sub(r),
+                "*" => l.checked_mul(r),
+                "/" => l.checked_div(r),
+                "%" => l.checked_rem(r),
+                _ => return Err(format!("line {}: can't apply '{}' to integers", line, op)),
+            };
+            result.map(Value::Int).ok_or_else(|| format!("line {}: arithmetic overflow", line))
+        },
+        (op, l, r) => Err(format!("line {}: can't apply '{}' to {} and {}", line, op, l.type_name(), r.type_name())),
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use self::lang::{Engine, Ast, Value};
+use rng::Rng;
+use terrain::TerrainHolder;
+use MapHolder;
+
+mod lang;
+
+pub use self::lang::INT;
+
+/// Scripts are loaded from the files with this extension, a script is named after its file.
+pub const SCRIPT_EXTENSION: &'static str = "rhai";
+
+#[derive(Debug)]
+pub enum ScriptError {
+    IO(String),
+    Parse(String),
+    /// There's no script with the name.
+    Unknown(String),
+    /// The script failed while running.
+    Run(String),
+}
+
+impl From<::std::io::Error> for ScriptError {
+    fn from(e: ::std::io::Error) -> Self {
+        ScriptError::IO(format!("{:?}", e))
+    }
+}
+
+/// What a script sees of an entity.
+#[derive(Clone, Debug, Default)]
+pub struct EntityView {
+    pub position: Option<(u32, u32, u32)>,
+    /// Current and maximum health.
+    pub health: Option<(i32, i32)>,
+    pub name: Option<String>,
+    pub player: bool,
+}
+
+/// The world as scripts see it during a run of the scripting system. Scripts read the snapshot
+/// of the entities and the map, their changes to the entities are collected here and applied by
+/// the system afterwards, so the scripts never touch the component storages.
+#[derive(Default)]
+pub struct ScriptState {
+    pub seed: u64,
+    pub turn: u64,
+    pub map: Option<MapHolder>,
+    pub terrain: Option<TerrainHolder>,
+    pub entities: HashMap<INT, EntityView>,
+    /// Random numbers of the current call, see `rng::entity_seed`.
+    pub rng: Option<Rng>,
+    /// Entities whose health was changed.
+    pub changed_health: Vec<INT>,
+    /// Moves by an offset, performed as `WantsToMove` intents.
+    pub moves: Vec<(INT, i32, i32, i32)>,
+    pub messages: Vec<String>,
+}
+
+pub type ScriptStateHolder = Arc<Mutex<ScriptState>>;
+
+fn to_array((x, y, z): (u32, u32, u32)) -> Value {
+    Value::Array(vec![Value::Int(x as INT), Value::Int(y as INT), Value::Int(z as INT)])
+}
+
+/// Returns the cell at the coordinates, if they're on the map.
+fn cell_at(state: &ScriptState, x: INT, y: INT, z: INT) -> Option<u8> {
+    let map = match state.map {
+        Some(ref m) => m.lock().unwrap(),
+        None => return None,
+    };
+
+    let (sx, sy, sz) = map.size();
+    if x < 0 || y < 0 || z < 0 || x >= sx as INT || y >= sy as INT || z >= sz as INT {
+        return None;
+    }
+    Some(map[(x as u32, y as u32, z as u32)])
+}
+
+/// The coordinates passed as the first three arguments.
+fn coords(args: &[Value]) -> Result<(INT, INT, INT), String> {
+    Ok((try!(args[0].as_int()), try!(args[1].as_int()), try!(args[2].as_int())))
+}
+
+/// Registers the functions scripts can call:
+///
+/// * `turn()`, `player()` - the current turn and the id of the player's entity, or -1.
+/// * `position(id)` - `[x, y, z]` or `()`, `name(id)`, `health(id)`, `max_health(id)`.
+/// * `entities_at(x, y, z)` - ids of the entities in the cell.
+/// * `terrain(x, y, z)` - name of the terrain, `is_passable(x, y, z)`.
+/// * `set_terrain(x, y, z, name)` - changes the map.
+/// * `set_health(id, health)` - health of 0 or less kills the entity.
+/// * `move_by(id, dx, dy)` - the entity tries to move on its next action.
+/// * `message(text)` - adds the text to the player's log.
+/// * `random(n)` - a number from 0 to n - 1, the same in a replay of the game.
+fn register_api(engine: &mut Engine, state: &ScriptStateHolder) {
+    let s = state.clone();
+    engine.register("turn", 0, move |_| Ok(Value::Int(s.lock().unwrap().turn as INT)));
+
+    let s = state.clone();
+    engine.register("player", 0, move |_| {
+        let state = s.lock().unwrap();
+        let player = state.entities.iter().find(|&(_, e)| e.player).map_or(-1, |(id, _)| *id);
+        Ok(Value::Int(player))
+    });
+
+    let s = state.clone();
+    engine.register("position", 1, move |args| {
+        let id = try!(args[0].as_int());
+        let state = s.lock().unwrap();
+        let p = state.entities.get(&id).and_then(|e| e.position).map_or(Value::Unit, to_array);
+        Ok(p)
+    });
+
+    let s = state.clone();
+    engine.register("name", 1, move |args| {
+        let id = try!(args[0].as_int());
+        let state = s.lock().unwrap();
+        let name = state.entities.get(&id).and_then(|e| e.name.clone()).unwrap_or_default();
+        Ok(Value::Str(name))
+    });
+
+    let s = state.clone();
+    engine.register("health", 1, move |args| {
+        let id = try!(args[0].as_int());
+        let state = s.lock().unwrap();
+        let h = state.entities.get(&id).and_then(|e| e.health).map_or(0, |h| h.0 as INT);
+        Ok(Value::Int(h))
+    });
+
+    let s = state.clone();
+    engine.register("max_health", 1, move |args| {
+        let id = try!(args[0].as_int());
+        let state = s.lock().unwrap();
+        let h = state.entities.get(&id).and_then(|e| e.health).map_or(0, |h| h.1 as INT);
+        Ok(Value::Int(h))
+    });
+
+    let s = state.clone();
+    engine.register("entities_at", 3, move |args| {
+        let (x, y, z) = try!(coords(args));
+        let state = s.lock().unwrap();
+        let mut ids: Vec<INT> = state.entities.iter()
+            .filter(|&(_, e)| e.position.map_or(false, |p| (p.0 as INT, p.1 as INT, p.2 as INT) == (x, y, z)))
+            .map(|(id, _)| *id)
+            .collect();
+        // the snapshot is a hash map, the order must be the same in replays
+        ids.sort();
+        Ok(Value::Array(ids.into_iter().map(Value::Int).collect()))
+    });
+
+    let s = state.clone();
+    engine.register("terrain", 3, move |args| {
+        let (x, y, z) = try!(coords(args));
+        let state = s.lock().unwrap();
+        let name = match (cell_at(&state, x, y, z), state.terrain.as_ref()) {
+            (Some(c), Some(t)) => t.get(c).name.clone(),
+            _ => String::new(),
+        };
+        Ok(Value::Str(name))
+    });
+
+    let s = state.clone();
+    engine.register("is_passable", 3, move |args| {
+        let (x, y, z) = try!(coords(args));
+        let state = s.lock().unwrap();
+        let passable = match (cell_at(&state, x, y, z), state.terrain.as_ref()) {
+            (Some(c), Some(t)) => t.is_passable(c),
+            _ => false,
+        };
+        Ok(Value::Bool(passable))
+    });
+
+    let s = state.clone();
+    engine.register("set_terrain", 4, move |args| {
+        let (x, y, z) = try!(coords(args));
+        let name = try!(args[3].as_str());
+        let state = s.lock().unwrap();
+        let cell = match state.terrain.as_ref().and_then(|t| t.cell_of(name)) {
+            Some(c) => c,
+            None => {
+                warn!("scripts can't place the unknown terrain '{}'", name);
+                return Ok(Value::Bool(false));
+            },
+        };
+
+        if cell_at(&state, x, y, z).is_none() {
+            return Ok(Value::Bool(false));
+        }
+        if let Some(ref m) = state.map {
+            m.lock().unwrap()[(x as u32, y as u32, z as u32)] = cell;
+        }
+        Ok(Value::Bool(true))
+    });
+
+    let s = state.clone();
+    engine.register("set_health", 2, move |args| {
+        let (id, health) = (try!(args[0].as_int()), try!(args[1].as_int()));
+        let mut state = s.lock().unwrap();
+        let changed = match state.entities.get_mut(&id).and_then(|e| e.health.as_mut()) {
+            Some(h) => {
+                h.0 = ::std::cmp::min(h.1, health as i32);
+                true
+            },
+            None => false,
+        };
+
+        if changed && !state.changed_health.contains(&id) {
+            state.changed_health.push(id);
+        }
+        Ok(Value::Unit)
+    });
+
+    let s = state.clone();
+    engine.register("move_by", 3, move |args| {
+        let (id, dx, dy) = (try!(args[0].as_int()), try!(args[1].as_int()), try!(args[2].as_int()));
+        s.lock().unwrap().moves.push((id, dx as i32, dy as i32, 0));
+        Ok(Value::Unit)
+    });
+
+    let s = state.clone();
+    engine.register("message", 1, move |args| {
+        let text = try!(args[0].as_str());
+        s.lock().unwrap().messages.push(text.to_owned());
+        Ok(Value::Unit)
+    });
+
+    let s = state.clone();
+    engine.register("random", 1, move |args| {
+        let n = try!(args[0].as_int());
+        let mut state = s.lock().unwrap();
+        let r = match state.rng.as_mut() {
+            Some(rng) if n > 0 => rng.gen_range(0, n as u32) as INT,
+            _ => 0,
+        };
+        Ok(Value::Int(r))
+    });
+}
+
+/// The scripts with the engine which runs them. Scripts are written in a small subset of Rhai,
+/// see `lang`, and define functions taking the id of an entity, e.g. `fn on_turn(id) { ... }`.
+pub struct ScriptRegistry {
+    engine: Engine,
+    scripts: HashMap<String, Ast>,
+    state: ScriptStateHolder,
+}
+
+pub type ScriptsHolder = Arc<Mutex<ScriptRegistry>>;
+
+impl ScriptRegistry {
+    pub fn new() -> Self {
+        let state = Arc::new(Mutex::new(ScriptState::default()));
+        let mut engine = Engine::new();
+        register_api(&mut engine, &state);
+
+        ScriptRegistry {
+            engine: engine,
+            scripts: HashMap::new(),
+            state: state,
+        }
+    }
+
+    /// Compiles the source and adds it as the script with the name, replacing the old one.
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        let ast = match self.engine.compile(source) {
+            Ok(ast) => ast,
+            Err(e) => return Err(ScriptError::Parse(format!("{}: {}", name, e))),
+        };
+        self.scripts.insert(name.to_owned(), ast);
+        Ok(())
+    }
+
+    /// Loads all scripts in the directory.
+    pub fn load_dir(&mut self, path: &str) -> Result<(), ScriptError> {
+        use std::fs::{self, File};
+        use std::io::Read;
+
+        for entry in try!(fs::read_dir(path)) {
+            let path = try!(entry).path();
+            if path.extension().map_or(true, |e| e != SCRIPT_EXTENSION) {
+                continue;
+            }
+
+            let name = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(n) => n.to_owned(),
+                None => continue,
+            };
+
+            let mut buf = String::new();
+            try!(try!(File::open(&path)).read_to_string(&mut buf));
+            try!(self.add(&name, &buf));
+        }
+
+        Ok(())
+    }
+
+    /// The state shared with the functions available to scripts.
+    pub fn state(&self) -> &ScriptStateHolder {
+        &self.state
+    }
+
+    /// Calls the function of the script with the id of an entity.
+    pub fn call(&self, name: &str, function: &str, id: INT) -> Result<(), ScriptError> {
+        let ast = match self.scripts.get(name) {
+            Some(ast) => ast,
+            None => return Err(ScriptError::Unknown(name.to_owned())),
+        };
+
+        match self.engine.call_fn(ast, function, vec![Value::Int(id)]) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ScriptError::Run(format!("{}::{}: {}", name, function, e))),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use specs;
+
+pub type Point = (u32, u32, u32);
+
+/// Finds the entities by their `Position`. The index is updated wherever the positions change:
+/// by the world when it creates or removes entities, and by the systems which move them.
+///
+/// All queries return the entities in the same order for the same world: by rows of cells, and
+/// in the order of their arrival within a cell.
+#[derive(Default)]
+pub struct SpatialIndex {
+    cells: HashMap<Point, Vec<specs::Entity>>,
+    positions: HashMap<specs::Entity, Point>,
+}
+
+pub type SpatialIndexHolder = Arc<Mutex<SpatialIndex>>;
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        SpatialIndex::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Adds the entity at the position, or moves it there if it's already indexed.
+    pub fn insert(&mut self, e: specs::Entity, p: Point) {
+        if self.positions.get(&e) == Some(&p) {
+            return;
+        }
+
+        self.remove(e);
+        self.cells.entry(p).or_insert_with(Vec::new).push(e);
+        self.positions.insert(e, p);
+    }
+
+    /// Removes the entity, e.g. when it loses its `Position`.
+    pub fn remove(&mut self, e: specs::Entity) {
+        let p = match self.positions.remove(&e) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let empty = match self.cells.get_mut(&p) {
+            Some(es) => {
+                es.retain(|x| *x != e);
+                es.is_empty()
+            },
+            None => false,
+        };
+        if empty {
+            self.cells.remove(&p);
+        }
+    }
+
+    pub fn position(&self, e: specs::Entity) -> Option<Point> {
+        self.positions.get(&e).cloned()
+    }
+
+    /// Returns the entities in the cell.
+    pub fn at(&self, p: Point) -> &[specs::Entity] {
+        match self.cells.get(&p) {
+            Some(es) => &es[..],
+            None => &[],
+        }
+    }
+
+    /// Returns the entities in the rectangle of the z-level which starts at `from` and has the
+    /// size `(width, height)`.
+    pub fn in_rect(&self, from: Point, (w, h): (u32, u32)) -> Vec<specs::Entity> {
+        let (x0, y0, z) = from;
+        let mut found = Vec::new();
+
+        // small areas are scanned cell by cell, large ones entity by entity
+        if (w as usize).saturating_mul(h as usize) <= self.cells.len() {
+            for y in y0..y0.saturating_add(h) {
+                for x in x0..x0.saturating_add(w) {
+                    found.extend_from_slice(self.at((x, y, z)));
+                }
+            }
+        } else {
+            let inside = |p: &Point| p.2 == z && p.0 >= x0 && p.0 - x0 < w && p.1 >= y0 && p.1 - y0 < h;
+
+            let mut cells: Vec<(&Point, &Vec<specs::Entity>)> = self.cells.iter()
+                .filter(|&(p, _)| inside(p))
+                .collect();
+            cells.sort_by_key(|&(p, _)| (p.1, p.0));
+
+            for (_, es) in cells {
+                found.extend_from_slice(es);
+            }
+        }
+
+        found
+    }
+
+    /// Returns the entities of the z-level whose distance from the center doesn't exceed the
+    /// radius. The distance is Euclidean, as in the field of view.
+    pub fn in_radius(&self, center: Point, radius: u32) -> Vec<specs::Entity> {
+        let (cx, cy, z) = center;
+        let from = (cx.saturating_sub(radius), cy.saturating_sub(radius), z);
+        // the rectangle is clamped to the coordinates, its size is computed in u64 so it can't overflow
+        let side = |c: u32, from: u32| {
+            cmp::min(c as u64 + radius as u64 - from as u64 + 1, ::std::u32::MAX as u64) as u32
+        };
+        let size = (side(cx, from.0), side(cy, from.1));
+        let r2 = radius as u64 * radius as u64;
+
+        let within = |p: Point| {
+            let d = |a: u32, b: u32| if a > b { (a - b) as u64 } else { (b - a) as u64 };
+            (d(p.0, cx) * d(p.0, cx)).saturating_add(d(p.1, cy) * d(p.1, cy)) <= r2
+        };
+
+        self.in_rect(from, size)
+            .into_iter()
+            .filter(|e| self.positions.get(e).map_or(false, |p| within(*p)))
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+use specs;
+use ai::{self, Action, Situation};
+use path::{self, Point};
+use rng::{self, Rng};
+use ::WorldContext;
+
+/// Lets monsters decide on their actions when a turn passes. Moves are attached as `WantsToMove`
+/// intents. The decisions depend only on the world's seed, the turn and the state of the world.
+pub struct AiSystem;
+
+impl AiSystem {
+    pub fn new() -> Self {
+        AiSystem
+    }
+}
+
+/// Stream of the random numbers used by the AI, see `rng::entity_seed`.
+const AI_STREAM: u64 = 1;
+
+impl specs::System<WorldContext> for AiSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use components::{Position, Ai, Viewer, Health, PlayerControlled, Energy, WantsToMove, Dead, StableId};
+
+        let (entities, pos_es, ai_es, viewer_es, health_es, player_es, mut energy_es, mut moves, dead_es, id_es) =
+            arg.fetch(|w| {
+                (w.entities(), w.read::<Position>(), w.read::<Ai>(), w.read::<Viewer>(), w.read::<Health>(),
+                 w.read::<PlayerControlled>(), w.write::<Energy>(), w.write::<WantsToMove>(), w.read::<Dead>(),
+                 w.read::<StableId>())
+            });
+
+        if !ctx.advance {
+            return;
+        }
+
+        let map = &ctx.map.lock().unwrap();
+        let cost = path::TerrainCost::new(map, &ctx.terrain);
+
+        let entities: Vec<specs::Entity> = entities.filter(|e| dead_es.get(*e).is_none()).collect();
+
+        let players: Vec<Point> = entities.iter()
+            .filter(|e| player_es.get(**e).is_some())
+            .filter_map(|e| pos_es.get(*e))
+            .map(|p| (p.x, p.y, p.z))
+            .collect();
+
+        for e in entities {
+            let (ai_c, pos) = match (ai_es.get(e), pos_es.get(e)) {
+                (Some(a), Some(p)) => (a, p),
+                _ => continue,
+            };
+
+            if energy_es.get(e).map_or(false, |en| !en.is_ready()) {
+                continue;
+            }
+
+            let position = (pos.x, pos.y, pos.z);
+
+            // monsters without eyes never notice the player
+            let player = viewer_es.get(e).and_then(|v| {
+                players.iter()
+                    .filter(|p| v.can_see(p.0, p.1, p.2))
+                    .min_by_key(|p| ai::distance(position, **p))
+                    .cloned()
+            });
+
+            let situation = Situation {
+                position: position,
+                map_size: map.size(),
+                player: player,
+                health: health_es.get(e).map(|h| h.fraction()),
+            };
+
+            let mut rng = Rng::new(rng::entity_seed(ctx.seed, ctx.turn, StableId::of(e, id_es.get(e)), AI_STREAM));
+            match ai::decide(&ai_c.behaviours, &cost, &situation, &mut rng) {
+                Action::Wait => {
+                    if let Some(en) = energy_es.get_mut(e) {
+                        en.spend();
+                    }
+                },
+                Action::Move((x, y, z)) => {
+                    let m = WantsToMove::new(x as i32 - pos.x as i32, y as i32 - pos.y as i32,
+                                             z as i32 - pos.z as i32);
+                    moves.insert(e, m);
+                },
+            }
+        }
+    }
+}
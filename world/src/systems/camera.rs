@@ -0,0 +1,114 @@
+use std::cmp::min;
+use std::sync::{Arc, Mutex};
+use specs;
+use systems::render::ViewHolder;
+use ::WorldContext;
+
+/// What the camera is looking at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraTarget {
+    /// The first entity controlled by the player.
+    Player,
+    Entity(specs::Entity),
+    Point(u32, u32, u32),
+    /// The view is not moved.
+    Locked,
+}
+
+pub struct Camera {
+    pub target: CameraTarget,
+    /// Minimal distance between the target and the edges of the view. The view is scrolled when
+    /// the target gets closer to an edge. Margins of half of the view size or more keep the target
+    /// in the center.
+    pub margins: (u32, u32),
+    /// Keep the view inside of the map.
+    pub clamp: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            target: CameraTarget::Player,
+            margins: (8, 5),
+            clamp: true,
+        }
+    }
+}
+
+pub type CameraHolder = Arc<Mutex<Camera>>;
+
+/// Calculates the new start of the view along one axis. `start` and `len` are the current
+/// view, `target` must stay at least `margin` cells away from the view's edges, `limit` is
+/// the size of the map or `None` if the view is not clamped.
+fn scroll(start: u32, len: u32, target: u32, margin: u32, limit: Option<u32>) -> u32 {
+    if len == 0 {
+        return start;
+    }
+
+    // the margin can't be larger than half of the view
+    let margin = min(margin, (len - 1) / 2);
+
+    let mut start = start;
+    if target < start + margin {
+        start = target.saturating_sub(margin);
+    } else if target + margin >= start + len {
+        start = target + margin + 1 - len;
+    }
+
+    match limit {
+        Some(limit) if start + len > limit => limit.saturating_sub(len),
+        _ => start,
+    }
+}
+
+/// Moves the view to follow the camera's target.
+pub struct CameraSystem {
+    camera: CameraHolder,
+    view: ViewHolder,
+}
+
+impl CameraSystem {
+    pub fn new(camera: CameraHolder, view: ViewHolder) -> Self {
+        CameraSystem {
+            camera: camera,
+            view: view,
+        }
+    }
+}
+
+impl specs::System<WorldContext> for CameraSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use specs::Join;
+        use components::{Position, PlayerControlled};
+
+        // locked before the components are fetched, so the renderer waits for the new position
+        let view = &mut self.view.lock().unwrap();
+
+        let (pos_es, player_es) = arg.fetch(|w| (w.read::<Position>(), w.read::<PlayerControlled>()));
+
+        let camera = self.camera.lock().unwrap();
+
+        let target = match camera.target {
+            CameraTarget::Player => (&pos_es, &player_es).iter().next().map(|(p, _)| (p.x, p.y, p.z)),
+            CameraTarget::Entity(e) => pos_es.get(e).map(|p| (p.x, p.y, p.z)),
+            CameraTarget::Point(x, y, z) => Some((x, y, z)),
+            CameraTarget::Locked => None,
+        };
+
+        let (tx, ty, tz) = match target {
+            Some(t) => t,
+            None => return,
+        };
+
+        let (map_x, map_y, _) = ctx.map.lock().unwrap().size();
+        let (limit_x, limit_y) = if camera.clamp { (Some(map_x), Some(map_y)) } else { (None, None) };
+
+        let (vx, vy, _) = view.position;
+        let (sx, sy, _) = view.size;
+
+        view.position = (scroll(vx, sx, tx, camera.margins.0, limit_x),
+                         scroll(vy, sy, ty, camera.margins.1, limit_y),
+                         tz);
+    }
+}
+
@@ -0,0 +1,169 @@
+use std::fmt;
+use std::sync::mpsc;
+use specs;
+use rng::{self, Rng};
+use components::{WantsToAttack, CombatStats, Health, Dead, Name, Energy, PlayerControlled, Item, Inventory,
+                 StableId};
+use messages::Severity;
+use ::WorldContext;
+
+/// Name of the player's entities, combat events are phrased in the second person for them.
+pub const PLAYER_NAME: &'static str = "you";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CombatOutcome {
+    Miss,
+    /// Contains the damage.
+    Hit(i32),
+    /// The target died of the damage.
+    Kill(i32),
+}
+
+/// Something that happened in a fight, reported to the UI. Events are also written to the
+/// message log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CombatEvent {
+    pub attacker: String,
+    pub target: String,
+    pub outcome: CombatOutcome,
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for CombatEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let second_person = self.attacker == PLAYER_NAME;
+        let verb = |base: &'static str, third: &'static str| if second_person { base } else { third };
+
+        let attacker = capitalize(&self.attacker);
+
+        match self.outcome {
+            CombatOutcome::Miss => write!(f, "{} {} {}.", attacker, verb("miss", "misses"), self.target),
+            CombatOutcome::Hit(d) => write!(f, "{} {} {} for {}.", attacker, verb("hit", "hits"), self.target, d),
+            CombatOutcome::Kill(_) => write!(f, "{} {} {}!", attacker, verb("kill", "kills"), self.target),
+        }
+    }
+}
+
+/// Stream of the random numbers used in fights, see `rng::entity_seed`.
+const COMBAT_STREAM: u64 = 2;
+
+/// Every point of defense lowers the chance to hit by this much.
+const DEFENSE_PENALTY: f32 = 0.05;
+
+/// Even the clumsiest attacker hits sometimes, even the best one misses sometimes.
+const MIN_HIT_CHANCE: f32 = 0.05;
+const MAX_HIT_CHANCE: f32 = 0.95;
+
+/// Resolves the `WantsToAttack` intents. Killed entities get the `Dead` component, they're
+/// removed by the world after the turn.
+pub struct CombatSystem {
+    events: mpsc::Sender<CombatEvent>,
+}
+
+impl CombatSystem {
+    pub fn new(events: mpsc::Sender<CombatEvent>) -> Self {
+        CombatSystem {
+            events: events,
+        }
+    }
+}
+
+impl specs::System<WorldContext> for CombatSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        let (entities, mut attacks, stats_es, mut health_es, mut dead_es, name_es, mut energy_es, player_es,
+             item_es, inventory_es, id_es) =
+            arg.fetch(|w| {
+                (w.entities(), w.write::<WantsToAttack>(), w.read::<CombatStats>(), w.write::<Health>(),
+                 w.write::<Dead>(), w.read::<Name>(), w.write::<Energy>(), w.read::<PlayerControlled>(),
+                 w.read::<Item>(), w.read::<Inventory>(), w.read::<StableId>())
+            });
+
+        let name_of = |e: specs::Entity| name_es.get(e).map_or("something".to_owned(), |n| n.name.clone());
+
+        // (power, defense) added by the equipped items
+        let bonus = |e: specs::Entity| inventory_es.get(e).map_or((0, 0), |inv| {
+            inv.items.iter()
+                .filter_map(|i| item_es.get(*i))
+                .filter(|i| i.equipped)
+                .fold((0, 0), |(p, d), i| (p + i.kind.power_bonus(), d + i.kind.defense_bonus()))
+        });
+        let mut done = Vec::new();
+
+        for e in entities {
+            let target = match attacks.get(e) {
+                Some(a) => a.target,
+                None => continue,
+            };
+            done.push(e);
+
+            // either of them could be killed earlier in the turn
+            if dead_es.get(e).is_some() || dead_es.get(target).is_some() {
+                continue;
+            }
+
+            let stats = match stats_es.get(e) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let power = stats.power + bonus(e).0;
+            let defense = stats_es.get(target).map_or(0, |s| s.defense) + bonus(target).1;
+            let chance = (stats.accuracy - defense as f32 * DEFENSE_PENALTY)
+                .max(MIN_HIT_CHANCE)
+                .min(MAX_HIT_CHANCE);
+
+            let mut rng = Rng::new(rng::entity_seed(ctx.seed, ctx.turn, StableId::of(e, id_es.get(e)), COMBAT_STREAM));
+
+            let mut outcome = CombatOutcome::Miss;
+            if let Some(h) = health_es.get_mut(target) {
+                if rng.chance(chance) {
+                    let damage = rng.gen_range(1, power.max(1) as u32 + 1) as i32;
+                    h.current -= damage;
+
+                    outcome = if h.current <= 0 {
+                        CombatOutcome::Kill(damage)
+                    } else {
+                        CombatOutcome::Hit(damage)
+                    };
+                }
+            }
+
+            if let CombatOutcome::Kill(_) = outcome {
+                dead_es.insert(target, Dead);
+            }
+
+            if let Some(en) = energy_es.get_mut(e) {
+                en.spend();
+            }
+
+            let event = CombatEvent {
+                attacker: name_of(e),
+                target: name_of(target),
+                outcome: outcome,
+            };
+
+            let severity = match (player_es.get(e).is_some(), player_es.get(target).is_some(), outcome) {
+                (_, true, CombatOutcome::Miss) => Severity::Info,
+                (_, true, _) => Severity::Danger,
+                (true, _, CombatOutcome::Kill(_)) => Severity::Good,
+                _ => Severity::Info,
+            };
+            ctx.log.lock().unwrap().add(&event.to_string(), severity, ctx.turn);
+
+            if let Err(e) = self.events.send(event) {
+                warn!("can't report the combat event: {:?}", e);
+            }
+        }
+
+        for e in done {
+            attacks.remove(e);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use specs;
+use ::WorldContext;
+
+/// Gives energy to every actor when a turn passes.
+pub struct EnergySystem;
+
+impl EnergySystem {
+    pub fn new() -> Self {
+        EnergySystem
+    }
+}
+
+impl specs::System<WorldContext> for EnergySystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use specs::Join;
+        use components::Energy;
+
+        let mut energy_es = arg.fetch(|w| w.write::<Energy>());
+
+        if !ctx.advance {
+            return;
+        }
+
+        for e in (&mut energy_es).iter() {
+            // actors can't save up energy while waiting
+            if !e.is_ready() {
+                e.value += e.speed as i32;
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use specs;
+use fov;
+use ::WorldContext;
+
+/// Updates the field of view of every `Viewer`. Everything the player's viewers see is stored in
+/// the map memory.
+pub struct FovSystem;
+
+impl FovSystem {
+    pub fn new() -> Self {
+        FovSystem
+    }
+}
+
+impl specs::System<WorldContext> for FovSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use specs::Join;
+        use components::{Position, Viewer, PlayerControlled};
+
+        let (pos_es, mut viewer_es, player_es) = arg.fetch(|w| {
+            (w.read::<Position>(), w.write::<Viewer>(), w.read::<PlayerControlled>())
+        });
+
+        let map = &ctx.map.lock().unwrap();
+        let terrain = &ctx.terrain;
+        let (sx, sy, sz) = map.size();
+
+        for (pos, viewer) in (&pos_es, &mut viewer_es).iter() {
+            viewer.visible.clear();
+
+            let z = pos.z;
+            if z >= sz {
+                continue;
+            }
+
+            let visible = &mut viewer.visible;
+            fov::compute((pos.x, pos.y), viewer.radius, (sx, sy),
+                         |x, y| terrain.blocks_sight(map[(x, y, z)]),
+                         |x, y| { visible.insert((x, y, z)); });
+        }
+
+        // the world replaces the memory together with the map
+        let memory = &mut ctx.memory.lock().unwrap();
+        for (viewer, _) in (&viewer_es, &player_es).iter() {
+            for &coords in &viewer.visible {
+                memory.remember(coords, map[coords]);
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use specs;
+use fov;
+use light::LightingHolder;
+use rng::{self, Rng};
+use ::WorldContext;
+
+/// Stream of the random numbers used for flickering, see `rng::entity_seed`.
+const FLICKER_STREAM: u64 = 3;
+
+/// Casts the light of every `LightSource`. The light doesn't pass through the terrain which
+/// blocks sight.
+pub struct LightingSystem {
+    lighting: LightingHolder,
+    /// Number of the updates, flickering changes with every one.
+    frame: u64,
+}
+
+impl LightingSystem {
+    pub fn new(lighting: LightingHolder) -> Self {
+        LightingSystem {
+            lighting: lighting,
+            frame: 0,
+        }
+    }
+}
+
+impl specs::System<WorldContext> for LightingSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use components::{Position, LightSource, StableId};
+
+        // locked before the components are fetched, so the renderer waits for the new light
+        let lighting = &mut self.lighting.lock().unwrap();
+
+        let (entities, pos_es, light_es, id_es) = arg.fetch(|w| {
+            (w.entities(), w.read::<Position>(), w.read::<LightSource>(), w.read::<StableId>())
+        });
+
+        self.frame += 1;
+
+        let map = &ctx.map.lock().unwrap();
+        let terrain = &ctx.terrain;
+        let (sx, sy, sz) = map.size();
+
+        lighting.clear();
+
+        for e in entities {
+            let (pos, light) = match (pos_es.get(e), light_es.get(e)) {
+                (Some(p), Some(l)) => (p, l),
+                _ => continue,
+            };
+
+            let z = pos.z;
+            if z >= sz {
+                continue;
+            }
+
+            let mut rng = Rng::new(rng::entity_seed(ctx.seed, self.frame, StableId::of(e, id_es.get(e)),
+                                                    FLICKER_STREAM));
+            let intensity = light.intensity * (1.0 - light.flicker * rng.next_f32());
+            let reach = light.radius as f32 + 1.0;
+
+            fov::compute((pos.x, pos.y), light.radius, (sx, sy),
+                         |x, y| terrain.blocks_sight(map[(x, y, z)]),
+                         |x, y| {
+                             let (dx, dy) = (x as f32 - pos.x as f32, y as f32 - pos.y as f32);
+                             let falloff = (1.0 - (dx * dx + dy * dy).sqrt() / reach).max(0.0);
+                             let k = intensity * falloff;
+                             lighting.add((x, y, z), [light.color[0] * k, light.color[1] * k, light.color[2] * k]);
+                         });
+        }
+    }
+}
@@ -1,2 +1,10 @@
 pub mod player_control;
+pub mod movement;
+pub mod ai;
+pub mod combat;
 pub mod render;
+pub mod fov;
+pub mod energy;
+pub mod camera;
+pub mod lighting;
+pub mod script;
@@ -0,0 +1,130 @@
+use std::sync::mpsc;
+use specs;
+use components::{Position, WantsToMove, WantsToAttack, BlocksMovement};
+use terrain::Stairs;
+use systems::player_control::{self, ObstacleChecker, PlayerCommand, Direction, CommandError, CommandReport};
+use ::WorldContext;
+
+/// Performs the `WantsToMove` intents of all entities, the player's included. A move fails if
+/// the target cell is an obstacle or is occupied by an entity which `BlocksMovement`. Moves
+/// between z-levels need stairs in the entity's cell. Moving into
+/// a hostile entity attacks it instead: the intent is replaced with `WantsToAttack`.
+pub struct MovementSystem<C: ObstacleChecker> {
+    checker: C,
+    /// Results of the player's moves.
+    reports: mpsc::Sender<CommandReport>,
+}
+
+impl<C: ObstacleChecker> MovementSystem<C> {
+    pub fn new(checker: C, reports: mpsc::Sender<CommandReport>) -> Self {
+        MovementSystem {
+            checker: checker,
+            reports: reports,
+        }
+    }
+
+    /// Returns the cell the move leads to, if the terrain allows it.
+    fn destination(&self, ctx: &WorldContext, p: &Position, m: &WantsToMove) -> Result<(u32, u32, u32), CommandError> {
+        let (x, y, z) = (p.x as i32 + m.dx, p.y as i32 + m.dy, p.z as i32 + m.dz);
+
+        if m.dz != 0 {
+            let map = ctx.map.lock().unwrap();
+            if z < 0 || z >= map.size().2 as i32 {
+                return Err(CommandError::NoLevel);
+            }
+
+            let needed = if m.dz < 0 { Stairs::Up } else { Stairs::Down };
+            if ctx.terrain.stairs(map[(p.x, p.y, p.z)]) != Some(needed) {
+                return Err(CommandError::NoStairs);
+            }
+        }
+
+        if !self.checker.check(x, y, z) {
+            return Err(CommandError::Blocked);
+        }
+
+        Ok((x as u32, y as u32, z as u32))
+    }
+}
+
+/// The player's command which results in the move.
+fn to_command(m: &WantsToMove) -> Option<PlayerCommand> {
+    match m.dz {
+        -1 => Some(PlayerCommand::Ascend),
+        1 => Some(PlayerCommand::Descend),
+        _ => Direction::from_delta(m.dx, m.dy).map(PlayerCommand::Move),
+    }
+}
+
+impl<C: ObstacleChecker> specs::System<WorldContext> for MovementSystem<C> {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use components::{PlayerControlled, Energy, Health, CombatStats, Dead};
+
+        let (entities, mut pos, mut moves, mut attacks, blocking, player, mut energy, health, stats, dead) =
+            arg.fetch(|w| {
+                (w.entities(), w.write::<Position>(), w.write::<WantsToMove>(), w.write::<WantsToAttack>(),
+                 w.read::<BlocksMovement>(), w.read::<PlayerControlled>(), w.write::<Energy>(),
+                 w.read::<Health>(), w.read::<CombatStats>(), w.read::<Dead>())
+            });
+
+        // the player fights monsters, monsters fight the player
+        let hostile = |a: specs::Entity, b: specs::Entity| {
+            player.get(a).is_some() != player.get(b).is_some() &&
+                stats.get(a).is_some() && health.get(b).is_some() && dead.get(b).is_none()
+        };
+
+        let entities: Vec<specs::Entity> = entities.collect();
+        let index = &mut ctx.spatial.lock().unwrap();
+        let mut done = Vec::new();
+
+        for e in entities {
+            let m = match moves.get(e) {
+                Some(m) => *m,
+                None => continue,
+            };
+            done.push(e);
+
+            let p = match pos.get_mut(e) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            let result = match self.destination(&ctx, p, &m) {
+                Ok(to) => {
+                    let blocker = index.at(to).iter().cloned().find(|o| *o != e && blocking.get(*o).is_some());
+
+                    match blocker {
+                        // the combat system takes the turn
+                        Some(target) if hostile(e, target) => {
+                            attacks.insert(e, WantsToAttack::new(target));
+                            Ok(())
+                        },
+                        // failed moves don't take the turn, so the entity can try something else
+                        Some(_) => Err(CommandError::Blocked),
+                        None => {
+                            *p = Position::new(to.0, to.1, to.2);
+                            index.insert(e, to);
+
+                            if let Some(en) = energy.get_mut(e) {
+                                en.spend();
+                            }
+                            Ok(())
+                        },
+                    }
+                },
+                Err(err) => Err(err),
+            };
+
+            if player.get(e).is_some() {
+                if let Some(cmd) = to_command(&m) {
+                    let report = CommandReport { command: cmd, result: result };
+                    player_control::send_report(&self.reports, &ctx, report);
+                }
+            }
+        }
+
+        for e in done {
+            moves.remove(e);
+        }
+    }
+}
@@ -1,13 +1,127 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::fmt;
+use std::sync::{mpsc, Arc, Mutex, RwLockReadGuard, RwLockWriteGuard};
 use specs;
 use map;
+use terrain;
+use components::{Position, WantsToMove, Dead, Health, Item, ItemKind, Inventory, WantsToRunScript};
+use spatial::SpatialIndex;
+use messages::Severity;
 use ::WorldContext;
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Direction {
+    /// (dx, dy), north is up.
+    pub fn delta(&self) -> (i32, i32) {
+        match *self {
+            Direction::North => (0, -1),
+            Direction::NorthEast => (1, -1),
+            Direction::East => (1, 0),
+            Direction::SouthEast => (1, 1),
+            Direction::South => (0, 1),
+            Direction::SouthWest => (-1, 1),
+            Direction::West => (-1, 0),
+            Direction::NorthWest => (-1, -1),
+        }
+    }
+
+    pub fn from_delta(dx: i32, dy: i32) -> Option<Direction> {
+        match (dx, dy) {
+            (0, -1) => Some(Direction::North),
+            (1, -1) => Some(Direction::NorthEast),
+            (1, 0) => Some(Direction::East),
+            (1, 1) => Some(Direction::SouthEast),
+            (0, 1) => Some(Direction::South),
+            (-1, 1) => Some(Direction::SouthWest),
+            (-1, 0) => Some(Direction::West),
+            (-1, -1) => Some(Direction::NorthWest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PlayerCommand {
-    MoveUp,
-    MoveDown,
-    MoveLeft,
-    MoveRight,
+    Move(Direction),
+    /// Skip a turn.
+    Wait,
+    /// Go one z-level up, the player must stand on stairs leading up.
+    Ascend,
+    /// Go one z-level down, the player must stand on stairs leading down.
+    Descend,
+    /// Pick up an item lying under the player.
+    PickUp,
+    /// Drop the item with the index in the player's inventory.
+    Drop(usize),
+    /// Use the item with the index in the player's inventory, e.g. drink a potion.
+    Use(usize),
+    /// Equip the item with the index in the player's inventory, or take it off if it's
+    /// already equipped.
+    Equip(usize),
+    /// Do whatever is appropriate with the adjacent cell, e.g. open or close a door.
+    Interact(Direction),
+    Open(Direction),
+    Close(Direction),
+}
+
+/// Why a command failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommandError {
+    /// The way is blocked by an obstacle or an entity.
+    Blocked,
+    /// There's no level in that direction.
+    NoLevel,
+    /// Going up or down needs the stairs in that direction.
+    NoStairs,
+    NothingToPickUp,
+    NothingToInteract,
+    NothingToOpen,
+    NothingToClose,
+    /// There's no item with the index in the inventory.
+    NoSuchItem,
+    /// The item would exceed the weight the player can carry.
+    TooHeavy,
+    CantUse,
+    CantEquip,
+    /// The player is dead and can't do anything.
+    Dead,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            CommandError::Blocked => "The way is blocked.",
+            CommandError::NoLevel => "There's nowhere to go.",
+            CommandError::NoStairs => "There are no stairs here.",
+            CommandError::NothingToPickUp => "There's nothing to pick up.",
+            CommandError::NothingToInteract => "There's nothing to do there.",
+            CommandError::NothingToOpen => "There's nothing to open.",
+            CommandError::NothingToClose => "There's nothing to close.",
+            CommandError::NoSuchItem => "You don't have that.",
+            CommandError::TooHeavy => "You can't carry that much.",
+            CommandError::CantUse => "You can't use that.",
+            CommandError::CantEquip => "You can't equip that.",
+            CommandError::Dead => "You are dead.",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The outcome of a player's command. Failed commands don't take the player's turn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CommandReport {
+    pub command: PlayerCommand,
+    pub result: Result<(), CommandError>,
 }
 
 pub trait ObstacleChecker: Send {
@@ -16,12 +130,14 @@ pub trait ObstacleChecker: Send {
 
 pub struct MapObstactChecker {
     map: Arc<Mutex<map::Map>>,
+    terrain: terrain::TerrainHolder,
 }
 
 impl MapObstactChecker {
-    pub fn new(map: Arc<Mutex<map::Map>>) -> Self {
+    pub fn new(map: Arc<Mutex<map::Map>>, terrain: terrain::TerrainHolder) -> Self {
         MapObstactChecker {
-            map: map
+            map: map,
+            terrain: terrain,
         }
     }
 }
@@ -47,59 +163,393 @@ impl ObstacleChecker for MapObstactChecker {
         let y = y as u32;
         let z = z as u32;
 
-        let t = m[(x, y, z)];
-        !(t == 201 || t == 205 || t == 187 || t == 186 || t == 199 || t == 217 || t == 179)
+        self.terrain.is_passable(m[(x, y, z)])
+    }
+}
+
+/// Sends the report of a command. Failures are also written to the message log.
+pub fn send_report(sender: &mpsc::Sender<CommandReport>, ctx: &WorldContext, report: CommandReport) {
+    if let Err(e) = report.result {
+        ctx.log.lock().unwrap().add(&e.to_string(), Severity::Warning, ctx.turn);
+    }
+
+    if let Err(e) = sender.send(report) {
+        warn!("can't report the command's result: {:?}", e);
     }
 }
 
-pub struct PlayerControlSystem<C: ObstacleChecker> {
+/// Turns the player's commands into actions. Moves are only attached as `WantsToMove` intents,
+/// they're performed by the movement system like those of any other entity. Item commands
+/// move the items between the floor and the player's `Inventory`.
+pub struct PlayerControlSystem {
     receiver: mpsc::Receiver<PlayerCommand>,
-    checker: C,
+    reports: mpsc::Sender<CommandReport>,
 }
 
-impl<C: ObstacleChecker> PlayerControlSystem<C> {
-    pub fn new(receiver: mpsc::Receiver<PlayerCommand>, checker: C) -> Self {
+impl PlayerControlSystem {
+    pub fn new(receiver: mpsc::Receiver<PlayerCommand>, reports: mpsc::Sender<CommandReport>) -> Self {
         PlayerControlSystem {
             receiver: receiver,
-            checker: checker,
+            reports: reports,
+        }
+    }
+}
+
+fn adjacent(p: &Position, dir: Direction) -> (i32, i32, i32) {
+    let (dx, dy) = dir.delta();
+    (p.x as i32 + dx, p.y as i32 + dy, p.z as i32)
+}
+
+fn opened(t: &terrain::Terrain) -> Option<map::Cell> {
+    t.opens_to
+}
+
+fn closed(t: &terrain::Terrain) -> Option<map::Cell> {
+    t.closes_to
+}
+
+/// Replaces an adjacent cell with the result of opening or closing it. Fails with `nothing` if
+/// the cell can't be changed that way.
+fn change_cell<F>(ctx: &WorldContext, (x, y, z): (i32, i32, i32), occupied: bool, f: F, nothing: CommandError)
+                  -> Result<(), CommandError>
+    where F: Fn(&terrain::Terrain) -> Option<map::Cell> {
+
+    let mut map = ctx.map.lock().unwrap();
+    let (sx, sy, sz) = map.size();
+    if x < 0 || y < 0 || z < 0 || x >= sx as i32 || y >= sy as i32 || z >= sz as i32 {
+        return Err(nothing);
+    }
+
+    let coords = (x as u32, y as u32, z as u32);
+    match f(ctx.terrain.get(map[coords])) {
+        Some(c) => {
+            // a closing door would squash whatever stands in it
+            if occupied && !ctx.terrain.is_passable(c) {
+                return Err(CommandError::Blocked);
+            }
+
+            map[coords] = c;
+            Ok(())
+        },
+        None => Err(nothing),
+    }
+}
+
+/// The commands grouped by what performs them.
+enum Action {
+    /// Attached as a `WantsToMove` intent, the movement system performs it and reports the result.
+    Move(WantsToMove),
+    /// Performed right away by `perform`.
+    Simple(SimpleCommand),
+    /// Moves the items between the floor and the player's `Inventory`, or uses them.
+    Item(ItemCommand),
+}
+
+#[derive(Clone, Copy)]
+enum SimpleCommand {
+    Wait,
+    Open(Direction),
+    Close(Direction),
+    Interact(Direction),
+}
+
+#[derive(Clone, Copy)]
+enum ItemCommand {
+    PickUp,
+    Drop(usize),
+    Use(usize),
+    Equip(usize),
+}
+
+fn action(cmd: PlayerCommand) -> Action {
+    match cmd {
+        PlayerCommand::Move(dir) => {
+            let (dx, dy) = dir.delta();
+            Action::Move(WantsToMove::new(dx, dy, 0))
+        },
+        PlayerCommand::Ascend => Action::Move(WantsToMove::new(0, 0, -1)),
+        PlayerCommand::Descend => Action::Move(WantsToMove::new(0, 0, 1)),
+        PlayerCommand::Wait => Action::Simple(SimpleCommand::Wait),
+        PlayerCommand::Open(dir) => Action::Simple(SimpleCommand::Open(dir)),
+        PlayerCommand::Close(dir) => Action::Simple(SimpleCommand::Close(dir)),
+        PlayerCommand::Interact(dir) => Action::Simple(SimpleCommand::Interact(dir)),
+        PlayerCommand::PickUp => Action::Item(ItemCommand::PickUp),
+        PlayerCommand::Drop(i) => Action::Item(ItemCommand::Drop(i)),
+        PlayerCommand::Use(i) => Action::Item(ItemCommand::Use(i)),
+        PlayerCommand::Equip(i) => Action::Item(ItemCommand::Equip(i)),
+    }
+}
+
+/// Performs a command which doesn't need an intent or items.
+fn perform(ctx: &WorldContext, cmd: SimpleCommand, p: &Position, index: &SpatialIndex) -> Result<(), CommandError> {
+    let occupied = |(x, y, z): (i32, i32, i32)| {
+        x >= 0 && y >= 0 && z >= 0 && !index.at((x as u32, y as u32, z as u32)).is_empty()
+    };
+
+    match cmd {
+        SimpleCommand::Wait => Ok(()),
+        SimpleCommand::Open(dir) => change_cell(ctx, adjacent(p, dir), false, opened, CommandError::NothingToOpen),
+        SimpleCommand::Close(dir) => {
+            let c = adjacent(p, dir);
+            change_cell(ctx, c, occupied(c), closed, CommandError::NothingToClose)
+        },
+        SimpleCommand::Interact(dir) => {
+            let c = adjacent(p, dir);
+            change_cell(ctx, c, false, opened, CommandError::NothingToInteract)
+                .or_else(|_| change_cell(ctx, c, occupied(c), closed, CommandError::NothingToInteract))
+        },
+    }
+}
+
+/// Performs the command for each of the player's entities. It succeeds if it succeeds for any of
+/// them.
+fn for_players<F>(players: &[(specs::Entity, Position)], mut f: F) -> Result<(), CommandError>
+    where F: FnMut(specs::Entity, &Position) -> Result<(), CommandError> {
+
+    let mut result = Err(CommandError::Blocked);
+    for &(e, ref p) in players {
+        let r = f(e, p);
+        if result.is_err() {
+            result = r;
+        }
+    }
+    result
+}
+
+/// Only one item of a kind can be equipped at a time, e.g. a single weapon.
+fn same_slot(a: &ItemKind, b: &ItemKind) -> bool {
+    match (*a, *b) {
+        (ItemKind::Weapon { .. }, ItemKind::Weapon { .. }) => true,
+        (ItemKind::Armor { .. }, ItemKind::Armor { .. }) => true,
+        _ => false,
+    }
+}
+
+type WriteStorage<'a, T> = specs::Storage<T, RwLockReadGuard<'a, specs::Allocator>,
+                                          RwLockWriteGuard<'a, specs::MaskedStorage<T>>>;
+
+/// The storages the item commands change.
+struct ItemStorages<'a> {
+    pos: WriteStorage<'a, Position>,
+    dead: WriteStorage<'a, Dead>,
+    items: WriteStorage<'a, Item>,
+    inventories: WriteStorage<'a, Inventory>,
+    health: WriteStorage<'a, Health>,
+    calls: WriteStorage<'a, WantsToRunScript>,
+}
+
+fn say(ctx: &WorldContext, text: String, severity: Severity) {
+    ctx.log.lock().unwrap().add(&text, severity, ctx.turn);
+}
+
+/// The item with the index in the inventory of `e`.
+fn carried(s: &ItemStorages, e: specs::Entity, idx: usize) -> Result<(specs::Entity, Item), CommandError> {
+    s.inventories.get(e)
+        .and_then(|inv| inv.items.get(idx).cloned())
+        .and_then(|i| s.items.get(i).map(|item| (i, item.clone())))
+        .ok_or(CommandError::NoSuchItem)
+}
+
+fn take_out(s: &mut ItemStorages, e: specs::Entity, idx: usize) {
+    if let Some(inv) = s.inventories.get_mut(e) {
+        inv.items.remove(idx);
+    }
+}
+
+fn pick_up(ctx: &WorldContext, e: specs::Entity, p: &Position, index: &mut SpatialIndex, s: &mut ItemStorages)
+           -> Result<(), CommandError> {
+
+    let (i, item) = try!(index.at((p.x, p.y, p.z))
+        .iter()
+        .filter_map(|i| s.items.get(*i).map(|item| (*i, item.clone())))
+        .next()
+        .ok_or(CommandError::NothingToPickUp));
+
+    let stack = {
+        let inv = try!(s.inventories.get(e).ok_or(CommandError::NothingToPickUp));
+        let weight: f32 = inv.items.iter()
+            .filter_map(|c| s.items.get(*c))
+            .map(|c| c.total_weight())
+            .sum();
+
+        if weight + item.total_weight() > inv.max_weight {
+            return Err(CommandError::TooHeavy);
+        }
+
+        inv.items.iter()
+            .cloned()
+            .find(|c| s.items.get(*c).map_or(false, |c| c.stacks_with(&item)))
+    };
+
+    s.pos.remove(i);
+    index.remove(i);
+
+    let stacked = match stack {
+        Some(c) => s.items.get_mut(c).map(|c| c.count += item.count).is_some(),
+        None => false,
+    };
+
+    // the picked up entity isn't needed anymore
+    if stacked {
+        s.dead.insert(i, Dead);
+    } else if let Some(inv) = s.inventories.get_mut(e) {
+        inv.items.push(i);
+    }
+
+    say(ctx, format!("You pick up {}.", item.describe()), Severity::Info);
+    Ok(())
+}
+
+fn drop_item(ctx: &WorldContext, e: specs::Entity, p: &Position, idx: usize, index: &mut SpatialIndex,
+             s: &mut ItemStorages) -> Result<(), CommandError> {
+
+    let (i, _) = try!(carried(s, e, idx));
+
+    take_out(s, e, idx);
+    s.pos.insert(i, p.clone());
+    index.insert(i, (p.x, p.y, p.z));
+
+    if let Some(item) = s.items.get_mut(i) {
+        item.equipped = false;
+        say(ctx, format!("You drop {}.", item.describe()), Severity::Info);
+    }
+    Ok(())
+}
+
+fn use_item(ctx: &WorldContext, e: specs::Entity, idx: usize, s: &mut ItemStorages) -> Result<(), CommandError> {
+    let (i, item) = try!(carried(s, e, idx));
+
+    match item {
+        Item { kind: ItemKind::Potion { heal }, ref name, .. } => {
+            if let Some(h) = s.health.get_mut(e) {
+                h.current = ::std::cmp::min(h.max, h.current + heal);
+            }
+            say(ctx, format!("You drink {} and feel better.", name), Severity::Good);
+        },
+        // the scripting system performs the effect
+        Item { script: Some(ref script), ref name, .. } => {
+            s.calls.insert(e, WantsToRunScript::new(script, "on_use"));
+            say(ctx, format!("You use {}.", name), Severity::Info);
+        },
+        _ => return Err(CommandError::CantUse),
+    }
+
+    // used items are used up
+    let count = match s.items.get_mut(i) {
+        Some(item) => {
+            item.count -= 1;
+            item.count
+        },
+        None => 0,
+    };
+    if count == 0 {
+        take_out(s, e, idx);
+        s.dead.insert(i, Dead);
+    }
+    Ok(())
+}
+
+fn equip(ctx: &WorldContext, e: specs::Entity, idx: usize, s: &mut ItemStorages) -> Result<(), CommandError> {
+    let (i, item) = try!(carried(s, e, idx));
+    if !item.is_equippable() {
+        return Err(CommandError::CantEquip);
+    }
+
+    let equip = !item.equipped;
+
+    if equip {
+        let others = s.inventories.get(e).map_or(vec![], |inv| inv.items.clone());
+        for c in others {
+            if let Some(other) = s.items.get_mut(c) {
+                if other.equipped && same_slot(&other.kind, &item.kind) {
+                    other.equipped = false;
+                    say(ctx, format!("You take off {}.", other.name), Severity::Info);
+                }
+            }
         }
     }
+
+    if let Some(item) = s.items.get_mut(i) {
+        item.equipped = equip;
+    }
+
+    let verb = if equip { "equip" } else { "take off" };
+    say(ctx, format!("You {} {}.", verb, item.name), Severity::Info);
+    Ok(())
 }
 
-impl<C: ObstacleChecker> specs::System<WorldContext> for PlayerControlSystem<C> {
-    fn run(&mut self, arg: specs::RunArg, _: WorldContext) {
+impl specs::System<WorldContext> for PlayerControlSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
         use specs::Join;
-        use components::{Position, PlayerControlled};
+        use components::{PlayerControlled, Energy};
 
-        let (mut pos, _) = arg.fetch(|w| (w.write::<Position>(), w.read::<PlayerControlled>()));
+        let (entities, pos, player, mut moves, mut energy, dead, items, inventories, health, calls) =
+            arg.fetch(|w| {
+                (w.entities(), w.write::<Position>(), w.read::<PlayerControlled>(), w.write::<WantsToMove>(),
+                 w.write::<Energy>(), w.write::<Dead>(), w.write::<Item>(), w.write::<Inventory>(),
+                 w.write::<Health>(), w.write::<WantsToRunScript>())
+            });
 
-        match self.receiver.try_recv() {
-            Ok(cmd) => {
-                for p in (&mut pos).iter() {
-                    let mut x = p.x as i32;
-                    let mut y = p.y as i32;
-                    let z = p.z as i32;
+        let cmd = match self.receiver.try_recv() {
+            Ok(cmd) => cmd,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(e) => panic!("Unhandled error while receiving player commands: {:?}", e),
+        };
 
-                    match cmd {
-                        PlayerCommand::MoveUp => y -= 1,
-                        PlayerCommand::MoveDown => y += 1,
-                        PlayerCommand::MoveLeft => x -= 1,
-                        PlayerCommand::MoveRight => x += 1,
-                    }
+        let entities: Vec<specs::Entity> = entities.collect();
+        let players: Vec<specs::Entity> = entities.iter()
+            .cloned()
+            .filter(|e| player.get(*e).is_some() && dead.get(*e).is_none())
+            .collect();
 
-                    if !self.checker.check(x, y, z) {
-                        continue;
-                    }
+        if players.is_empty() {
+            let report = CommandReport { command: cmd, result: Err(CommandError::Dead) };
+            send_report(&self.reports, &ctx, report);
+            return;
+        }
 
-                    let x = x as u32;
-                    let y = y as u32;
+        let placed: Vec<(specs::Entity, Position)> = players.iter()
+            .filter_map(|e| pos.get(*e).map(|p| (*e, p.clone())))
+            .collect();
 
-                    p.x = x;
-                    p.y = y;
+        let mut storages = ItemStorages {
+            pos: pos,
+            dead: dead,
+            items: items,
+            inventories: inventories,
+            health: health,
+            calls: calls,
+        };
+
+        let index = &mut ctx.spatial.lock().unwrap();
+
+        let result = match action(cmd) {
+            // the movement system reports the result
+            Action::Move(intent) => {
+                for e in players {
+                    moves.insert(e, intent);
                 }
+                return;
             },
-            Err(mpsc::TryRecvError::Empty) => (),
-            Err(e) => panic!("Unhandled error while receiving player commands: {:?}", e),
+            Action::Simple(c) => for_players(&placed, |_, p| perform(&ctx, c, p, &index)),
+            Action::Item(c) => for_players(&placed, |e, p| {
+                match c {
+                    ItemCommand::PickUp => pick_up(&ctx, e, p, index, &mut storages),
+                    ItemCommand::Drop(i) => drop_item(&ctx, e, p, i, index, &mut storages),
+                    ItemCommand::Use(i) => use_item(&ctx, e, i, &mut storages),
+                    ItemCommand::Equip(i) => equip(&ctx, e, i, &mut storages),
+                }
+            }),
+        };
+
+        // successful actions take the player's turn
+        if result.is_ok() {
+            for (e, _) in (&mut energy, &player).iter() {
+                e.spend();
+            }
         }
+
+        let report = CommandReport { command: cmd, result: result };
+        send_report(&self.reports, &ctx, report);
     }
 }
@@ -1,9 +1,11 @@
 use std::sync::{Arc, Mutex};
 use specs;
 use tile;
+use light::LightingHolder;
 use ::WorldContext;
 
 /// Defines the size of the rendered part of the world.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct View {
     pub position: (u32, u32, u32),
     pub size: (u32, u32, u32),
@@ -40,13 +42,15 @@ pub type RenderedViewHolder = Arc<Mutex<Option<RenderedView>>>;
 pub struct RenderingSystem {
     view: ViewHolder,
     render: RenderedViewHolder,
+    lighting: LightingHolder,
 }
 
 impl RenderingSystem {
-    pub fn new(render: RenderedViewHolder, view: ViewHolder) -> Self {
+    pub fn new(render: RenderedViewHolder, view: ViewHolder, lighting: LightingHolder) -> Self {
         RenderingSystem {
             view: view,
             render: render,
+            lighting: lighting,
         }
     }
 
@@ -58,14 +62,31 @@ impl RenderingSystem {
 impl specs::System<WorldContext> for RenderingSystem {
     fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
         use std::cmp::min;
-        use components::{Position, Visible};
+        use components::{Position, Visible, Viewer, PlayerControlled, BlocksMovement};
         use tile::Effect;
         use specs::Join;
 
         let view = &self.view.lock().unwrap();
 
+        // the positions are fetched, even though the index finds the entities, so the rendering
+        // waits for the systems which move them
+        let (_pos_es, vis_es, viewer_es, player_es, blocking_es) = arg.fetch(|w| {
+            (w.read::<Position>(), w.read::<Visible>(), w.read::<Viewer>(), w.read::<PlayerControlled>(),
+             w.read::<BlocksMovement>())
+        });
+
+        // tiles are visible if any of the player's entities can see them. If there are no
+        // such entities, the whole world is visible
+        let viewers: Vec<&Viewer> = (&viewer_es, &player_es).iter().map(|(v, _)| v).collect();
+        let is_visible = |x, y, z| viewers.is_empty() || viewers.iter().any(|v| v.can_see(x, y, z));
+
         let (mut tiles, position, size) = {
+            // the lighting system holds the lighting while it locks the map, so the same order here
+            let lighting = &self.lighting.lock().unwrap();
             let map = &ctx.map.lock().unwrap();
+            let memory = &ctx.memory.lock().unwrap();
+            // the memory can be outdated if the map was just replaced
+            let memory_valid = memory.size() == map.size();
             let (map_size_x, map_size_y, map_size_z) = map.size();
 
             let (start_x, start_y, start_z) = view.position;
@@ -81,8 +102,17 @@ impl specs::System<WorldContext> for RenderingSystem {
             for z in start_z..end_z {
                 for y in start_y..end_y {
                     for x in start_x..end_x {
-                        let m = map[(x, y, z)];
-                        tiles.push(tile::Tile::new(m));
+                        let t = if is_visible(x, y, z) {
+                            let mut t = tile::Tile::new(map[(x, y, z)], true, true);
+                            t.add_effect(Effect::Lit(lighting.light_at((x, y, z))));
+                            t
+                        } else {
+                            match if memory_valid { memory.get((x, y, z)) } else { None } {
+                                Some(m) => tile::Tile::new(m, false, true),
+                                None => tile::Tile::new(0, false, false),
+                            }
+                        };
+                        tiles.push(t);
                     }
                 }
             }
@@ -90,26 +120,43 @@ impl specs::System<WorldContext> for RenderingSystem {
             (tiles, view.position, (end_x - start_x, end_y - start_y, end_z - start_z))
         };
 
-        let (pos_es, vis_es) = arg.fetch(|w| {
-            (w.read::<Position>(), w.read::<Visible>())
-        });
+        // (layer, tile index, mark). Actors, which block movement, stand on top of the items
+        // and corpses lying on the floor
+        let mut marks = Vec::new();
 
-        for (pos, vis) in (&pos_es, &vis_es).iter() {
-            let &Position { x, y, z } = pos;
+        let index = ctx.spatial.lock().unwrap();
 
-            if x < position.0 || x >= position.0 + size.0 {
-                continue;
-            }
-            if y < position.1 || y >= position.1 + size.1 {
-                continue;
-            }
-            if z < position.2 || z >= position.2 + size.2 {
-                continue;
+        for level in position.2..position.2 + size.2 {
+            for e in index.in_rect((position.0, position.1, level), (size.0, size.1)) {
+                // the offsets come from the index like the query, entities outside the view are skipped
+                let (vis, (px, py, pz)) = match (vis_es.get(e), index.position(e)) {
+                    (Some(v), Some(p)) => (v, p),
+                    _ => continue,
+                };
+
+                if px < position.0 || py < position.1 || pz < position.2 {
+                    continue;
+                }
+                let (x, y, z) = (px - position.0, py - position.1, pz - position.2);
+                if x >= size.0 || y >= size.1 || z >= size.2 {
+                    continue;
+                }
+                let idx = (x + y * size.0 + z * size.0 * size.1) as usize;
+
+                // entities out of sight are not shown
+                if !tiles[idx].visible {
+                    continue;
+                }
+
+                let layer = if blocking_es.get(e).is_some() { 1 } else { 0 };
+                marks.push((layer, idx, vis.mark));
             }
+        }
 
-            let idx = (x + y * size.0 + z * size.0 * size.1) as usize;
-            let t = &mut tiles[idx];
-            t.add_effect(Effect::Marked(vis.mark));
+        // the topmost mark comes first, that's the one the frontends draw
+        marks.sort_by(|a, b| b.0.cmp(&a.0));
+        for (_, idx, mark) in marks {
+            tiles[idx].add_effect(Effect::Marked(mark));
         }
 
         let render = RenderedView {
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use specs;
+use rng::{self, Rng};
+use script::{ScriptsHolder, EntityView, INT};
+use messages::Severity;
+use ::WorldContext;
+
+/// Stream of the random numbers used by scripts, see `rng::entity_seed`.
+const SCRIPT_STREAM: u64 = 4;
+
+/// Calls the scripts: the `on_turn` of the entities with a `Script` when a turn passes and they
+/// can act, the `on_enter` of the terrain's trigger when an entity steps on it, and the functions
+/// requested by the `WantsToRunScript` intents. Scripts see a snapshot of the entities, their
+/// changes are applied after all calls.
+pub struct ScriptSystem {
+    scripts: ScriptsHolder,
+    /// Positions of the entities in the previous run, to notice when they step on a trigger.
+    positions: HashMap<specs::Entity, (u32, u32, u32)>,
+}
+
+impl ScriptSystem {
+    pub fn new(scripts: ScriptsHolder) -> Self {
+        ScriptSystem {
+            scripts: scripts,
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl specs::System<WorldContext> for ScriptSystem {
+    fn run(&mut self, arg: specs::RunArg, ctx: WorldContext) {
+        use components::{Position, Health, Name, PlayerControlled, Script, WantsToRunScript, WantsToMove, Energy,
+                         Dead, StableId};
+
+        let (entities, pos_es, mut health_es, name_es, player_es, script_es, mut requests, mut moves,
+             mut energy_es, mut dead_es, id_es) =
+            arg.fetch(|w| {
+                (w.entities(), w.read::<Position>(), w.write::<Health>(), w.read::<Name>(),
+                 w.read::<PlayerControlled>(), w.read::<Script>(), w.write::<WantsToRunScript>(),
+                 w.write::<WantsToMove>(), w.write::<Energy>(), w.write::<Dead>(), w.read::<StableId>())
+            });
+
+        let all: Vec<specs::Entity> = entities.collect();
+        let entities: Vec<specs::Entity> = all.iter().cloned().filter(|e| dead_es.get(*e).is_none()).collect();
+
+        // (entity, script, function)
+        let mut calls: Vec<(specs::Entity, String, String)> = Vec::new();
+
+        for &e in &all {
+            if let Some(r) = requests.get(e).cloned() {
+                if dead_es.get(e).is_none() {
+                    calls.push((e, r.script, r.function));
+                }
+                requests.remove(e);
+            }
+        }
+
+        {
+            let map = ctx.map.lock().unwrap();
+            let mut positions = HashMap::new();
+
+            for &e in &entities {
+                let p = match pos_es.get(e) {
+                    Some(p) => (p.x, p.y, p.z),
+                    None => continue,
+                };
+                positions.insert(e, p);
+
+                let moved = self.positions.get(&e).map_or(false, |old| *old != p);
+                if let (true, Some(trigger)) = (moved, ctx.terrain.trigger(map[p])) {
+                    calls.push((e, trigger.to_owned(), "on_enter".to_owned()));
+                }
+            }
+
+            self.positions = positions;
+        }
+
+        let mut actors = Vec::new();
+        if ctx.advance {
+            for &e in &entities {
+                let s = match script_es.get(e) {
+                    Some(s) => s,
+                    None => continue,
+                };
+
+                if energy_es.get(e).map_or(false, |en| !en.is_ready()) {
+                    continue;
+                }
+
+                calls.push((e, s.name.clone(), "on_turn".to_owned()));
+                actors.push(e);
+            }
+        }
+
+        if calls.is_empty() {
+            return;
+        }
+
+        let ids: HashMap<INT, specs::Entity> = entities.iter().map(|e| (e.get_id() as INT, *e)).collect();
+
+        let scripts = self.scripts.lock().unwrap();
+        let state = scripts.state().clone();
+
+        {
+            let mut s = state.lock().unwrap();
+            s.seed = ctx.seed;
+            s.turn = ctx.turn;
+            s.map = Some(ctx.map.clone());
+            s.terrain = Some(ctx.terrain.clone());
+            s.changed_health.clear();
+            s.moves.clear();
+            s.messages.clear();
+            s.entities = ids.iter().map(|(id, e)| {
+                let view = EntityView {
+                    position: pos_es.get(*e).map(|p| (p.x, p.y, p.z)),
+                    health: health_es.get(*e).map(|h| (h.current, h.max)),
+                    name: name_es.get(*e).map(|n| n.name.clone()),
+                    player: player_es.get(*e).is_some(),
+                };
+                (*id, view)
+            }).collect();
+        }
+
+        for (e, script, function) in calls {
+            let seed = rng::entity_seed(ctx.seed, ctx.turn, StableId::of(e, id_es.get(e)), SCRIPT_STREAM);
+            state.lock().unwrap().rng = Some(Rng::new(seed));
+
+            if let Err(err) = scripts.call(&script, &function, e.get_id() as INT) {
+                warn!("script error: {:?}", err);
+            }
+        }
+
+        let mut guard = state.lock().unwrap();
+        let s = &mut *guard;
+
+        for id in s.changed_health.drain(..) {
+            let (e, current) = match (ids.get(&id), s.entities.get(&id).and_then(|v| v.health)) {
+                (Some(e), Some(h)) => (*e, h.0),
+                _ => continue,
+            };
+
+            if let Some(h) = health_es.get_mut(e) {
+                h.current = current;
+            }
+            if current <= 0 {
+                dead_es.insert(e, Dead);
+            }
+        }
+
+        for (id, dx, dy, dz) in s.moves.drain(..) {
+            if let Some(e) = ids.get(&id) {
+                moves.insert(*e, WantsToMove::new(dx, dy, dz));
+            }
+        }
+
+        for text in s.messages.drain(..) {
+            ctx.log.lock().unwrap().add(&text, Severity::Info, ctx.turn);
+        }
+
+        // a script which doesn't move its entity makes it wait
+        for e in actors {
+            if moves.get(e).is_none() {
+                if let Some(en) = energy_es.get_mut(e) {
+                    en.spend();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use cfg;
+use map;
+
+/// Where stairs lead. Up is the previous z-level, down is the next one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stairs {
+    Up,
+    Down,
+}
+
+/// Properties of a terrain type.
+pub struct Terrain {
+    pub name: String,
+    pub description: String,
+    pub passable: bool,
+    pub blocks_sight: bool,
+    pub movement_cost: f32,
+    pub fg: [u8; 4],
+    pub bg: [u8; 4],
+    /// The cell which replaces this one when it's opened.
+    pub opens_to: Option<map::Cell>,
+    /// The cell which replaces this one when it's closed.
+    pub closes_to: Option<map::Cell>,
+    pub stairs: Option<Stairs>,
+    /// Name of the script called when an entity steps on the terrain.
+    pub trigger: Option<String>,
+}
+
+impl Terrain {
+    /// `opens_to` and `closes_to` are resolved after all terrain types are known.
+    fn from_cfg(c: &cfg::terrain::TerrainCfg) -> Result<Self, cfg::CfgError> {
+        let stairs = match c.stairs.as_ref().map(|s| s.as_ref()) {
+            Some("up") => Some(Stairs::Up),
+            Some("down") => Some(Stairs::Down),
+            Some(other) => {
+                return Err(cfg::CfgError::Invalid(format!("Unknown stairs direction of '{}': '{}'", c.name, other)))
+            },
+            None => None,
+        };
+
+        Ok(Terrain {
+            name: c.name.clone(),
+            description: c.description.clone(),
+            passable: c.passable,
+            blocks_sight: c.blocks_sight,
+            movement_cost: c.movement_cost,
+            fg: c.fg.unwrap_or([255, 255, 255, 255]),
+            bg: c.bg.unwrap_or([0, 0, 0, 255]),
+            opens_to: None,
+            closes_to: None,
+            stairs: stairs,
+            trigger: c.trigger.clone(),
+        })
+    }
+}
+
+const CELL_COUNT: usize = 256;
+
+/// Maps each `map::Cell` to its terrain type.
+pub struct TerrainRegistry {
+    terrains: Vec<Terrain>,
+    /// first cell of each terrain type
+    canonical_cells: Vec<map::Cell>,
+    /// index of the terrain type of each cell
+    by_cell: Vec<usize>,
+}
+
+pub type TerrainHolder = Arc<TerrainRegistry>;
+
+impl TerrainRegistry {
+    pub fn from_cfg(c: &cfg::terrain::TerrainSetCfg) -> Result<Self, cfg::CfgError> {
+        use cfg::CfgError;
+
+        let default = match c.terrains.iter().position(|t| t.name == c.default) {
+            Some(i) => i,
+            None => return Err(CfgError::Invalid(format!("Unknown default terrain: '{}'", c.default))),
+        };
+
+        let mut by_cell: Vec<Option<usize>> = vec![None; CELL_COUNT];
+        let mut canonical_cells = Vec::with_capacity(c.terrains.len());
+
+        for (i, t) in c.terrains.iter().enumerate() {
+            if c.terrains[..i].iter().any(|other| other.name == t.name) {
+                return Err(CfgError::Invalid(format!("Duplicate terrain: '{}'", t.name)));
+            }
+
+            if t.cells.is_empty() {
+                return Err(CfgError::Invalid(format!("Terrain '{}' has no cells", t.name)));
+            }
+
+            for cell in &t.cells {
+                let slot = &mut by_cell[*cell as usize];
+                if let Some(other) = *slot {
+                    return Err(CfgError::Invalid(format!("Cell {} is defined in both '{}' and '{}'",
+                                                         cell, c.terrains[other].name, t.name)));
+                }
+                *slot = Some(i);
+            }
+
+            canonical_cells.push(t.cells[0]);
+        }
+
+        let mut terrains = Vec::with_capacity(c.terrains.len());
+        for t in &c.terrains {
+            terrains.push(try!(Terrain::from_cfg(t)));
+        }
+
+        {
+            let resolve = |name: &Option<String>| -> Result<Option<map::Cell>, CfgError> {
+                match *name {
+                    Some(ref n) => match c.terrains.iter().position(|t| t.name == *n) {
+                        Some(i) => Ok(Some(canonical_cells[i])),
+                        None => Err(CfgError::Invalid(format!("Unknown terrain: '{}'", n))),
+                    },
+                    None => Ok(None),
+                }
+            };
+
+            for (t, tc) in terrains.iter_mut().zip(c.terrains.iter()) {
+                t.opens_to = try!(resolve(&tc.opens_to));
+                t.closes_to = try!(resolve(&tc.closes_to));
+            }
+        }
+
+        Ok(TerrainRegistry {
+            terrains: terrains,
+            canonical_cells: canonical_cells,
+            by_cell: by_cell.into_iter().map(|t| t.unwrap_or(default)).collect(),
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, cfg::CfgError> {
+        let c = try!(cfg::terrain::load(path));
+        TerrainRegistry::from_cfg(&c)
+    }
+
+    pub fn get(&self, cell: map::Cell) -> &Terrain {
+        &self.terrains[self.by_cell[cell as usize]]
+    }
+
+    /// Returns the cell which should be used to place the specified terrain on a map.
+    pub fn cell_of(&self, name: &str) -> Option<map::Cell> {
+        self.terrains.iter()
+            .position(|t| t.name == name)
+            .map(|i| self.canonical_cells[i])
+    }
+
+    pub fn is_passable(&self, cell: map::Cell) -> bool {
+        self.get(cell).passable
+    }
+
+    pub fn blocks_sight(&self, cell: map::Cell) -> bool {
+        self.get(cell).blocks_sight
+    }
+
+    pub fn movement_cost(&self, cell: map::Cell) -> f32 {
+        self.get(cell).movement_cost
+    }
+
+    pub fn stairs(&self, cell: map::Cell) -> Option<Stairs> {
+        self.get(cell).stairs
+    }
+
+    /// Returns the name of the script triggered by stepping on the cell.
+    pub fn trigger(&self, cell: map::Cell) -> Option<&str> {
+        self.get(cell).trigger.as_ref().map(|s| s.as_ref())
+    }
+
+    /// Returns the cell used to place stairs in the direction on a map.
+    pub fn stairs_cell(&self, dir: Stairs) -> Option<map::Cell> {
+        self.terrains.iter()
+            .position(|t| t.stairs == Some(dir))
+            .map(|i| self.canonical_cells[i])
+    }
+}
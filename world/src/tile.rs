@@ -3,7 +3,8 @@ use map;
 
 #[derive(Debug)]
 pub enum Effect {
-    Lit(f32),
+    /// Colour of the light falling on the tile, see `light::Lighting`.
+    Lit(::light::Color),
     Marked(u8),
 }
 
@@ -11,13 +12,20 @@ pub enum Effect {
 pub struct Tile {
     pub ground: map::Cell,
     pub effects: Option<Vec<Effect>>,
+    /// Is the tile in the field of view of the player.
+    pub visible: bool,
+    /// Was the tile ever seen by the player. For explored tiles out of sight, `ground` is
+    /// the last known state of the cell.
+    pub explored: bool,
 }
 
 impl Tile {
-    pub fn new(ground: map::Cell) -> Self {
+    pub fn new(ground: map::Cell, visible: bool, explored: bool) -> Self {
         Tile {
             ground: ground,
             effects: None,
+            visible: visible,
+            explored: explored,
         }
     }
 
@@ -30,6 +38,14 @@ impl Tile {
             v.push(e);
         }
     }
+
+    /// Returns the light falling on the tile, if it's known.
+    pub fn light(&self) -> Option<::light::Color> {
+        self.effects.as_ref().and_then(|fx| fx.iter().filter_map(|e| match *e {
+            Effect::Lit(c) => Some(c),
+            _ => None,
+        }).next())
+    }
 }
 
 pub struct TilesIter<'a> {